@@ -1,5 +1,4 @@
-use super::Algorithm;
-use crate::gurobi::ffi::{GurobiOptimizer, GurobiVar};
+use super::{Algorithm, Formulation};
 
 pub struct AssetFairness {
     prices: Vec<f64>,
@@ -15,32 +14,30 @@ fn dot_product(a: &[f64], b: &[f64]) -> f64 {
     product
 }
 
+impl AssetFairness {
+    pub fn new(prices: Vec<f64>) -> AssetFairness {
+        AssetFairness { prices }
+    }
+}
+
 impl Algorithm for AssetFairness {
     fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        self.formulate(resources, demands).unwrap().solve()
+    }
+
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
         let num_resources = resources.len();
         assert!(num_resources == self.prices.len());
         for demand in demands {
             assert!(demand.len() == self.prices.len());
         }
-        let mut optimizer = GurobiOptimizer::new("mip1");
-        let coeffs: Vec<GurobiVar> = demands
-            .iter()
-            .map(|_| optimizer.add_var('C', true))
-            .collect();
-
-        // Add constraint for each type of resources.
-        for i in 0..num_resources {
-            optimizer.add_constraint(
-                &coeffs,
-                &demands.iter().map(|demand| demand[i]).collect::<Vec<f64>>(),
-                '<',
-                resources[i],
-            );
-        }
+        let mut formulation = Formulation::new("asset_fairness", demands.len());
+        formulation.add_capacity_constraints(resources, demands);
 
         // Every user spends the same.
+        let coeffs = &formulation.coeffs;
         for i in 0..demands.len() - 1 {
-            optimizer.add_constraint(
+            formulation.optimizer.add_named_constraint(
                 &[coeffs[i], coeffs[i + 1]],
                 &[
                     dot_product(&demands[i], &self.prices),
@@ -48,14 +45,10 @@ impl Algorithm for AssetFairness {
                 ],
                 '=',
                 0.0,
+                &format!("equal_spending_{}_{}", i, i + 1),
             )
         }
-        optimizer.optimize("max");
-
-        coeffs
-            .iter()
-            .map(|var| *optimizer.solutions.get(&var).unwrap())
-            .collect()
+        Some(formulation)
     }
 }
 
@@ -0,0 +1,249 @@
+use super::{Algorithm, Formulation};
+
+/// Largest coefficient of `demand` that fits in `available`, 0 if it needs
+/// nothing.
+pub(super) fn fit(available: &[f64], demand: &[f64]) -> f64 {
+    let coeff = demand
+        .iter()
+        .zip(available)
+        .filter(|(d, _)| **d > 0.0)
+        .map(|(d, a)| f64::max(*a, 0.0) / d)
+        .fold(f64::INFINITY, f64::min);
+    if coeff.is_finite() {
+        coeff
+    } else {
+        0.0
+    }
+}
+
+/// Amount of each resource the coefficients use.
+fn used(num_resources: usize, demands: &[Vec<f64>], coeffs: &[f64]) -> Vec<f64> {
+    (0..num_resources)
+        .map(|j| demands.iter().zip(coeffs).map(|(d, x)| d[j] * x).sum())
+        .collect()
+}
+
+/// Max-min fair division of `capacity` among `claims`: nobody gets more than
+/// it claims, and what one does not use is split equally among the others.
+fn water_fill(capacity: f64, claims: &[f64]) -> Vec<f64> {
+    let mut order: Vec<usize> = (0..claims.len()).collect();
+    order.sort_by(|a, b| claims[*a].total_cmp(&claims[*b]));
+    let mut shares = vec![0.0; claims.len()];
+    let mut left = capacity;
+    for (k, i) in order.iter().enumerate() {
+        shares[*i] = f64::min(claims[*i], left / (claims.len() - k) as f64);
+        left -= shares[*i];
+    }
+    shares
+}
+
+/// Splits every resource equally among the tenants, whether they need it or
+/// not.
+pub struct StaticPartition {}
+
+impl Algorithm for StaticPartition {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        let partition: Vec<f64> = resources.iter().map(|r| r / demands.len() as f64).collect();
+        demands
+            .iter()
+            .map(|demand| fit(&partition, demand))
+            .collect()
+    }
+}
+
+/// Max-min fair on every resource on its own, ignoring that a tenant can
+/// only use its share of one resource in proportion to its share of the
+/// others. A tenant claims as much of a resource as it could use with the
+/// whole device to itself, and whatever its tightest share does not let it
+/// use is wasted.
+pub struct PerResourceMaxMin {}
+
+impl Algorithm for PerResourceMaxMin {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        let alone: Vec<f64> = demands
+            .iter()
+            .map(|demand| fit(resources, demand))
+            .collect();
+        let shares: Vec<Vec<f64>> = resources
+            .iter()
+            .enumerate()
+            .map(|(j, resource)| {
+                let claims: Vec<f64> = demands
+                    .iter()
+                    .zip(&alone)
+                    .map(|(demand, x)| demand[j] * x)
+                    .collect();
+                water_fill(*resource, &claims)
+            })
+            .collect();
+        demands
+            .iter()
+            .enumerate()
+            .map(|(i, demand)| {
+                let share: Vec<f64> = shares.iter().map(|shares| shares[i]).collect();
+                fit(&share, demand)
+            })
+            .collect()
+    }
+}
+
+/// Serves demands in order, each getting its full demand, or what is left
+/// of it, before the next one gets anything.
+pub struct Fifo {}
+
+impl Algorithm for Fifo {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        let mut left = resources.to_vec();
+        demands
+            .iter()
+            .map(|demand| {
+                let coeff = f64::min(1.0, fit(&left, demand));
+                for (left, d) in left.iter_mut().zip(demand) {
+                    *left -= d * coeff;
+                }
+                coeff
+            })
+            .collect()
+    }
+}
+
+/// Utilitarian: maximizes the total number of units served, whoever serves
+/// them.
+pub struct MaxThroughput {}
+
+impl Algorithm for MaxThroughput {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        self.formulate(resources, demands).unwrap().solve()
+    }
+
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
+        for demand in demands {
+            assert!(demand.len() == resources.len());
+        }
+        let mut formulation = Formulation::new("max_throughput", demands.len());
+        formulation.add_capacity_constraints(resources, demands);
+        Some(formulation)
+    }
+}
+
+/// Splits the most loaded resource, relative to its capacity, equally among
+/// the tenants that need it, scaled down if that overflows another resource.
+/// Tenants that do not need the bottleneck split what is left equally.
+pub struct BottleneckFairness {}
+
+impl Algorithm for BottleneckFairness {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        let load = |j: usize| {
+            let total: f64 = demands.iter().map(|demand| demand[j]).sum();
+            if total > 0.0 {
+                total / resources[j]
+            } else {
+                0.0
+            }
+        };
+        let bottleneck = (0..resources.len())
+            .max_by(|a, b| load(*a).total_cmp(&load(*b)))
+            .unwrap();
+        let needing = demands
+            .iter()
+            .filter(|demand| demand[bottleneck] > 0.0)
+            .count();
+
+        let mut coeffs: Vec<f64> = demands
+            .iter()
+            .map(|demand| {
+                if demand[bottleneck] > 0.0 {
+                    resources[bottleneck] / needing as f64 / demand[bottleneck]
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+        let scale = used(resources.len(), demands, &coeffs)
+            .iter()
+            .zip(resources)
+            .filter(|(used, _)| **used > 0.0)
+            .map(|(used, resource)| resource / used)
+            .fold(1.0, f64::min);
+        for coeff in coeffs.iter_mut() {
+            *coeff *= scale;
+        }
+
+        let others = demands.len() - needing;
+        if others > 0 {
+            let left: Vec<f64> = used(resources.len(), demands, &coeffs)
+                .iter()
+                .zip(resources)
+                .map(|(used, resource)| (resource - used) / others as f64)
+                .collect();
+            for (coeff, demand) in coeffs.iter_mut().zip(demands) {
+                if demand[bottleneck] <= 0.0 {
+                    *coeff = fit(&left, demand);
+                }
+            }
+        }
+        coeffs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::assert_alloc;
+
+    #[test]
+    fn water_fill() {
+        assert_eq!(super::water_fill(9.0, &[2.0, 10.0, 4.0]), [2.0, 3.5, 3.5]);
+        assert_eq!(super::water_fill(9.0, &[1.0, 2.0]), [1.0, 2.0]);
+    }
+
+    #[test]
+    fn heuristics() {
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        assert_alloc(
+            &StaticPartition {}.allocate(&resources, &demands),
+            &[2.25, 1.5],
+        );
+        // Tenant 1 gets 4.5 of the first resource but can only use 1.5 * 3.
+        assert_alloc(
+            &PerResourceMaxMin {}.allocate(&resources, &demands),
+            &[3.75, 1.5],
+        );
+        assert_alloc(&Fifo {}.allocate(&resources, &demands), &[1.0, 1.0]);
+        // Equal halves of the first resource overflow the second one.
+        assert_alloc(
+            &BottleneckFairness {}.allocate(&resources, &demands),
+            &[4.15, 1.38],
+        );
+    }
+
+    #[test]
+    fn fifo() {
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![4.0, 16.0], vec![3.0, 1.0], vec![4.0, 2.0]];
+        assert_alloc(&Fifo {}.allocate(&resources, &demands), &[1.0, 1.0, 0.5]);
+    }
+
+    #[test]
+    fn bottleneck_bypass() {
+        // The last tenant does not need the bottleneck and uses what the
+        // others leave of the second resource.
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![1.0, 1.0], vec![3.0, 1.0], vec![0.0, 2.0]];
+        assert_alloc(
+            &BottleneckFairness {}.allocate(&resources, &demands),
+            &[4.5, 1.5, 6.0],
+        );
+    }
+
+    #[test]
+    fn max_throughput() {
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        assert_alloc(
+            &MaxThroughput {}.allocate(&resources, &demands),
+            &[4.09, 1.63],
+        );
+    }
+}
@@ -1,35 +1,20 @@
-use super::Algorithm;
-use crate::gurobi::ffi::{GurobiOptimizer, GurobiVar};
+use super::{Algorithm, Formulation};
 
 pub struct Ceei {}
 
 impl Algorithm for Ceei {
     fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        self.formulate(resources, demands).unwrap().solve()
+    }
+
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
         let num_resources = resources.len();
         for demand in demands {
             assert!(demand.len() == num_resources);
         }
-        let mut optimizer = GurobiOptimizer::new("mip1");
-        let coeffs: Vec<GurobiVar> = demands
-            .iter()
-            .map(|_| optimizer.add_var('C', true))
-            .collect();
-
-        // Add constraint for each type of resources.
-        for i in 0..num_resources {
-            optimizer.add_constraint(
-                &coeffs,
-                &demands.iter().map(|demand| demand[i]).collect::<Vec<f64>>(),
-                '<',
-                resources[i],
-            );
-        }
-        optimizer.optimize("max");
-
-        coeffs
-            .iter()
-            .map(|var| *optimizer.solutions.get(&var).unwrap())
-            .collect()
+        let mut formulation = Formulation::new("ceei", demands.len());
+        formulation.add_capacity_constraints(resources, demands);
+        Some(formulation)
     }
 }
 
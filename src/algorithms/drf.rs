@@ -1,29 +1,19 @@
-use super::Algorithm;
-use crate::gurobi::ffi::{GurobiOptimizer, GurobiVar};
+use super::{Algorithm, Formulation};
 
 pub struct Drf {}
 
 impl Algorithm for Drf {
     fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        self.formulate(resources, demands).unwrap().solve()
+    }
+
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
         let num_resources = resources.len();
         for demand in demands {
             assert!(demand.len() == num_resources);
         }
-        let mut optimizer = GurobiOptimizer::new("mip1");
-        let coeffs: Vec<GurobiVar> = demands
-            .iter()
-            .map(|_| optimizer.add_var('C', true))
-            .collect();
-
-        // Add constraint for each type of resources.
-        for i in 0..num_resources {
-            optimizer.add_constraint(
-                &coeffs,
-                &demands.iter().map(|demand| demand[i]).collect::<Vec<f64>>(),
-                '<',
-                resources[i],
-            );
-        }
+        let mut formulation = Formulation::new("drf", demands.len());
+        formulation.add_capacity_constraints(resources, demands);
 
         // Compute dominant shares for each demand.
         let mut dominant_shares: Vec<f64> = Vec::new();
@@ -38,20 +28,17 @@ impl Algorithm for Drf {
         }
 
         // Equalize dominant shares.
+        let coeffs = &formulation.coeffs;
         for i in 0..demands.len() - 1 {
-            optimizer.add_constraint(
+            formulation.optimizer.add_named_constraint(
                 &[coeffs[i], coeffs[i + 1]],
                 &[dominant_shares[i], -dominant_shares[i + 1]],
                 '=',
                 0.0,
+                &format!("equal_share_{}_{}", i, i + 1),
             );
         }
-        optimizer.optimize("max");
-
-        coeffs
-            .iter()
-            .map(|var| *optimizer.solutions.get(&var).unwrap())
-            .collect()
+        Some(formulation)
     }
 }
 
@@ -0,0 +1,285 @@
+use super::{from_name, Algorithm};
+use crate::stats;
+use std::collections::BTreeMap;
+
+/// A group of demands at one level of a hierarchy, e.g. a tenant, one of its
+/// applications or one of their flows, and what it got.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Group {
+    /// Level of the group, 0 for the top one.
+    pub level: usize,
+    /// Index of the group it belongs to among the groups of the allocation,
+    /// None at the top level.
+    pub parent: Option<usize>,
+    /// Identifier of the group within its parent, e.g. the tenant index.
+    pub id: usize,
+    /// Sum of the demands in the group.
+    pub demand: Vec<f64>,
+    /// What its parent got, or the capacity at the top level, which the
+    /// group shared with its siblings.
+    pub capacity: Vec<f64>,
+    /// What the group got.
+    pub alloc: Vec<f64>,
+}
+
+impl Group {
+    /// Largest share of the capacity it shared with its siblings the group
+    /// got of any resource.
+    pub fn dominant_share(&self) -> f64 {
+        self.alloc
+            .iter()
+            .zip(&self.capacity)
+            .filter(|(_, capacity)| **capacity > 0.0)
+            .map(|(alloc, capacity)| alloc / capacity)
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Outcome of a hierarchical allocation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HierarchicalAllocation {
+    /// Coefficient of each demand.
+    pub coeffs: Vec<f64>,
+    /// Every group, parents before their children.
+    pub groups: Vec<Group>,
+}
+
+impl HierarchicalAllocation {
+    /// How evenly the groups at `level` split what their parent got: Jain's
+    /// index of their dominant shares, averaged over the parents with at
+    /// least two groups at `level`. None if there is no such parent.
+    pub fn fairness(&self, level: usize) -> Option<f64> {
+        let mut siblings: BTreeMap<Option<usize>, Vec<f64>> = BTreeMap::new();
+        for group in self.groups.iter().filter(|group| group.level == level) {
+            siblings
+                .entry(group.parent)
+                .or_default()
+                .push(group.dominant_share());
+        }
+        let indices: Vec<f64> = siblings
+            .values()
+            .filter(|shares| shares.len() > 1)
+            .map(|shares| stats::jain_index(shares))
+            .collect();
+        if indices.is_empty() {
+            None
+        } else {
+            Some(stats::mean(&indices))
+        }
+    }
+}
+
+/// Allocates down a hierarchy of groups, e.g. tenants, then their
+/// applications, then their flows. The top level is shared by a given
+/// algorithm; every group's allocation is then shared among its subgroups by
+/// the algorithm of the level below, named as from_name() takes them and
+/// created on the group's allocation. Demands in the same bottom group get
+/// the same coefficient, i.e. share its allocation in proportion to their
+/// demands.
+pub struct Hierarchy {
+    below: Vec<String>,
+}
+
+impl Hierarchy {
+    /// A hierarchy with the algorithms named `below` under the top level,
+    /// from top to bottom.
+    pub fn new(below: &[String]) -> Result<Hierarchy, String> {
+        for name in below {
+            if from_name(name, &[1.0]).is_none() {
+                return Err(format!("{}: unknown algorithm", name));
+            }
+        }
+        Ok(Hierarchy {
+            below: below.to_vec(),
+        })
+    }
+
+    /// Number of levels, including the top one.
+    pub fn num_levels(&self) -> usize {
+        self.below.len() + 1
+    }
+
+    /// Allocates `resources` to `demands`, where `paths[i]` has the group
+    /// demand i belongs to at every level, from the top down. `top` shares
+    /// the resources among the top groups.
+    pub fn allocate(
+        &self,
+        top: &dyn Algorithm,
+        resources: &[f64],
+        demands: &[Vec<f64>],
+        paths: &[Vec<usize>],
+    ) -> HierarchicalAllocation {
+        assert!(demands.len() == paths.len());
+        for path in paths {
+            assert!(path.len() >= self.num_levels());
+        }
+        let mut allocation = HierarchicalAllocation {
+            coeffs: vec![0.0; demands.len()],
+            groups: Vec::new(),
+        };
+        let members: Vec<usize> = (0..demands.len()).collect();
+        self.split(
+            Some(top),
+            0,
+            None,
+            resources,
+            &members,
+            demands,
+            paths,
+            &mut allocation,
+        );
+        allocation
+    }
+
+    /// Shares `capacity` among the groups at `level` of the demands
+    /// `members`, then recurses into every group.
+    #[allow(clippy::too_many_arguments)]
+    fn split(
+        &self,
+        alg: Option<&dyn Algorithm>,
+        level: usize,
+        parent: Option<usize>,
+        capacity: &[f64],
+        members: &[usize],
+        demands: &[Vec<f64>],
+        paths: &[Vec<usize>],
+        allocation: &mut HierarchicalAllocation,
+    ) {
+        let mut groups: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for i in members {
+            groups.entry(paths[*i][level]).or_default().push(*i);
+        }
+        let aggregates: Vec<Vec<f64>> = groups
+            .values()
+            .map(|members| {
+                (0..capacity.len())
+                    .map(|j| members.iter().map(|i| demands[*i][j]).sum())
+                    .collect()
+            })
+            .collect();
+        let coeffs = match alg {
+            Some(alg) => alg.allocate(capacity, &aggregates),
+            None => share(&self.below[level - 1], capacity, &aggregates),
+        };
+
+        for ((id, members), (demand, coeff)) in
+            groups.iter().zip(aggregates.into_iter().zip(coeffs))
+        {
+            let alloc: Vec<f64> = demand.iter().map(|d| d * coeff).collect();
+            allocation.groups.push(Group {
+                level,
+                parent,
+                id: *id,
+                demand,
+                capacity: capacity.to_vec(),
+                alloc: alloc.clone(),
+            });
+            if level + 1 < self.num_levels() {
+                let index = allocation.groups.len() - 1;
+                self.split(
+                    None,
+                    level + 1,
+                    Some(index),
+                    &alloc,
+                    members,
+                    demands,
+                    paths,
+                    allocation,
+                );
+            } else {
+                for i in members {
+                    allocation.coeffs[*i] = coeff;
+                }
+            }
+        }
+    }
+}
+
+/// Shares `capacity` among `demands` with the algorithm called `name`,
+/// created on the resources there is some of. Demands needing a resource
+/// there is none of get nothing, demands needing nothing get all of it.
+fn share(name: &str, capacity: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+    let available: Vec<usize> = (0..capacity.len()).filter(|j| capacity[*j] > 0.0).collect();
+    let mut coeffs = vec![0.0; demands.len()];
+    let mut sharing: Vec<usize> = Vec::new();
+    for (i, demand) in demands.iter().enumerate() {
+        let blocked = (0..capacity.len()).any(|j| demand[j] > 0.0 && capacity[j] <= 0.0);
+        if blocked {
+            continue;
+        }
+        if demand.iter().all(|d| *d <= 0.0) {
+            coeffs[i] = 1.0;
+        } else {
+            sharing.push(i);
+        }
+    }
+    if sharing.is_empty() {
+        return coeffs;
+    }
+    let resources: Vec<f64> = available.iter().map(|j| capacity[*j]).collect();
+    let shared_demands: Vec<Vec<f64>> = sharing
+        .iter()
+        .map(|i| available.iter().map(|j| demands[*i][*j]).collect())
+        .collect();
+    let alg = from_name(name, &resources).unwrap();
+    for (i, coeff) in sharing
+        .into_iter()
+        .zip(alg.allocate(&resources, &shared_demands))
+    {
+        coeffs[i] = coeff;
+    }
+    coeffs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::EqualScale;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn hierarchical() {
+        // Tenant 0 has two applications, the second with two flows, tenant 1
+        // a single flow.
+        let hierarchy = Hierarchy::new(&[
+            "static_partition".to_string(),
+            "static_partition".to_string(),
+        ])
+        .unwrap();
+        let demands = vec![
+            vec![2.0, 1.0],
+            vec![1.0, 1.0],
+            vec![1.0, 3.0],
+            vec![2.0, 2.0],
+        ];
+        let paths = vec![vec![0, 0, 0], vec![0, 1, 0], vec![0, 1, 1], vec![1, 0, 0]];
+        let allocation =
+            hierarchy.allocate(&EqualScale(f64::INFINITY), &[12.0, 14.0], &demands, &paths);
+        // The tenants get twice their demands, [8, 10] and [4, 4]. Within
+        // tenant 0, each application gets [4, 5] to fit its demand in: the
+        // first one twice its [2, 1], the second one 1.25 times its [2, 4].
+        // Its flows then get [1.25, 2.5] each.
+        let expected = [2.0, 1.25, 2.5 / 3.0, 2.0];
+        for (coeff, expected) in allocation.coeffs.iter().zip(&expected) {
+            assert!(
+                approx_eq!(f64, *coeff, *expected, epsilon = 1e-9),
+                "{:?}",
+                allocation.coeffs
+            );
+        }
+        assert_eq!(allocation.groups.len(), 9);
+        assert_eq!(allocation.groups[3].alloc, [2.5, 5.0]);
+        assert_eq!(allocation.groups[3].capacity, [8.0, 10.0]);
+
+        assert!(approx_eq!(
+            f64,
+            allocation.fairness(0).unwrap(),
+            stats::jain_index(&[10.0 / 14.0, 4.0 / 12.0]),
+            epsilon = 1e-9
+        ));
+        // Both applications of tenant 0 got half of what it got.
+        assert_eq!(allocation.fairness(1), Some(1.0));
+        assert!(allocation.fairness(3).is_none());
+        assert!(Hierarchy::new(&["lottery".to_string()]).is_err());
+    }
+}
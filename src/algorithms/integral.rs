@@ -0,0 +1,283 @@
+use super::{from_name, Algorithm, Formulation};
+use std::fmt;
+
+/// Weight of the total share in the objective of the integral allocators,
+/// relative to the smallest share. Small enough that the smallest share is
+/// maximized first, and units left over then go to whoever still fits.
+const TIE_BREAK_WEIGHT: f64 = 1e-3;
+
+/// DRF over indivisible units: every demand gets a whole number of units,
+/// maximizing the smallest dominant share. Exact equalization is rarely
+/// possible with whole units, so this is the max-min version of DRF.
+pub struct IntegralDrf {}
+
+impl IntegralDrf {
+    /// Dominant share of a single unit of each demand.
+    pub fn shares(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        demands
+            .iter()
+            .map(|demand| {
+                demand
+                    .iter()
+                    .zip(resources)
+                    .map(|(d, r)| d / r)
+                    .fold(0.0, f64::max)
+            })
+            .collect()
+    }
+}
+
+impl Algorithm for IntegralDrf {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        self.formulate(resources, demands).unwrap().solve()
+    }
+
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
+        Some(max_min_formulation(
+            "integral_drf",
+            resources,
+            demands,
+            &self.shares(resources, demands),
+        ))
+    }
+}
+
+/// Asset fairness over indivisible units: every demand gets a whole number
+/// of units, maximizing the smallest spending at the given prices.
+pub struct IntegralAssetFairness {
+    prices: Vec<f64>,
+}
+
+impl IntegralAssetFairness {
+    pub fn new(prices: Vec<f64>) -> IntegralAssetFairness {
+        IntegralAssetFairness { prices }
+    }
+
+    /// Price of a single unit of each demand.
+    pub fn shares(&self, demands: &[Vec<f64>]) -> Vec<f64> {
+        demands
+            .iter()
+            .map(|demand| demand.iter().zip(&self.prices).map(|(d, p)| d * p).sum())
+            .collect()
+    }
+}
+
+impl Algorithm for IntegralAssetFairness {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        self.formulate(resources, demands).unwrap().solve()
+    }
+
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
+        assert!(resources.len() == self.prices.len());
+        Some(max_min_formulation(
+            "integral_asset_fairness",
+            resources,
+            demands,
+            &self.shares(demands),
+        ))
+    }
+}
+
+/// The MIP maximizing the smallest `shares[i] * x_i` over whole numbers of
+/// units `x_i` that fit in `resources`, with a variable `min_share` for the
+/// smallest share.
+fn max_min_formulation(
+    name: &str,
+    resources: &[f64],
+    demands: &[Vec<f64>],
+    shares: &[f64],
+) -> Formulation {
+    for demand in demands {
+        assert!(demand.len() == resources.len());
+    }
+    let weights: Vec<f64> = shares
+        .iter()
+        .map(|share| TIE_BREAK_WEIGHT * share / demands.len() as f64)
+        .collect();
+    let mut formulation = Formulation::integral(name, &weights);
+    formulation.add_capacity_constraints(resources, demands);
+
+    let min_share = formulation
+        .optimizer
+        .add_weighted_var('C', 1.0, "min_share");
+    for (i, share) in shares.iter().enumerate() {
+        formulation.optimizer.add_named_constraint(
+            &[formulation.coeffs[i], min_share],
+            &[*share, -1.0],
+            '>',
+            0.0,
+            &format!("min_share_{}", i),
+        );
+    }
+    formulation
+}
+
+/// Algorithms with an integral variant, see Approximation::solve().
+pub const INTEGRAL_NAMES: &[&str] = &["drf", "asset_fairness"];
+
+/// How the integral allocation of an algorithm compares to its fractional
+/// allocation of the same instance.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Approximation {
+    /// Share of a single unit of each demand, as the algorithm measures it.
+    pub shares: Vec<f64>,
+    pub fractional: Vec<f64>,
+    pub integral: Vec<f64>,
+}
+
+impl Approximation {
+    /// Solves the instance with algorithm `name`, one of INTEGRAL_NAMES, and
+    /// with its integral variant.
+    pub fn solve(
+        name: &str,
+        resources: &[f64],
+        demands: &[Vec<f64>],
+    ) -> Result<Approximation, String> {
+        let shares = match name {
+            "drf" => IntegralDrf {}.shares(resources, demands),
+            "asset_fairness" => {
+                IntegralAssetFairness::new(resources.iter().map(|r| 1.0 / r).collect())
+                    .shares(demands)
+            }
+            _ => {
+                return Err(format!(
+                    "{}: no integral variant, expected one of {}",
+                    name,
+                    INTEGRAL_NAMES.join(", ")
+                ))
+            }
+        };
+        let fractional = from_name(name, resources)
+            .unwrap()
+            .allocate(resources, demands);
+        let integral = from_name(&format!("integral_{}", name), resources)
+            .unwrap()
+            .allocate(resources, demands);
+        Ok(Approximation {
+            shares,
+            fractional,
+            integral,
+        })
+    }
+
+    fn min_share(&self, coeffs: &[f64]) -> f64 {
+        self.shares
+            .iter()
+            .zip(coeffs)
+            .map(|(share, coeff)| share * coeff)
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    fn total_share(&self, coeffs: &[f64]) -> f64 {
+        self.shares
+            .iter()
+            .zip(coeffs)
+            .map(|(share, coeff)| share * coeff)
+            .sum()
+    }
+
+    pub fn fractional_min_share(&self) -> f64 {
+        self.min_share(&self.fractional)
+    }
+
+    pub fn integral_min_share(&self) -> f64 {
+        self.min_share(&self.integral)
+    }
+
+    pub fn fractional_total_share(&self) -> f64 {
+        self.total_share(&self.fractional)
+    }
+
+    pub fn integral_total_share(&self) -> f64 {
+        self.total_share(&self.integral)
+    }
+
+    /// Fraction of the fractional optimum's smallest share the integral
+    /// allocation achieves, 1 if both are zero.
+    pub fn ratio(&self) -> f64 {
+        let fractional = self.fractional_min_share();
+        if fractional <= 0.0 {
+            return 1.0;
+        }
+        self.integral_min_share() / fractional
+    }
+}
+
+impl fmt::Display for Approximation {
+    /// Lists both allocations of every demand, then their smallest and total
+    /// shares.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (fractional, integral)) in self.fractional.iter().zip(&self.integral).enumerate() {
+            writeln!(
+                f,
+                "tenant_{}: fractional {:.4}, integral {}",
+                i, fractional, integral
+            )?;
+        }
+        writeln!(
+            f,
+            "min_share: fractional {:.4}, integral {:.4}, ratio {:.4}",
+            self.fractional_min_share(),
+            self.integral_min_share(),
+            self.ratio()
+        )?;
+        writeln!(
+            f,
+            "total_share: fractional {:.4}, integral {:.4}",
+            self.fractional_total_share(),
+            self.integral_total_share()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::assert_alloc;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn drf() {
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        let alloc = IntegralDrf {}.allocate(&resources, &demands);
+        assert_alloc(&alloc, &[3.0, 2.0]);
+    }
+
+    #[test]
+    fn asset_fairness() {
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        let alg = IntegralAssetFairness::new(vec![2.0, 1.0]);
+        // Spending 18 and 14, where the fractional optimum spends 15.1 each.
+        let alloc = alg.allocate(&resources, &demands);
+        assert_alloc(&alloc, &[3.0, 2.0]);
+    }
+
+    #[test]
+    fn approximation() {
+        let approximation = Approximation {
+            shares: vec![6.0, 7.0],
+            fractional: vec![2.52, 2.16],
+            integral: vec![3.0, 2.0],
+        };
+        assert!(approx_eq!(
+            f64,
+            approximation.fractional_min_share(),
+            15.12,
+            epsilon = 1e-9
+        ));
+        assert_eq!(approximation.integral_min_share(), 14.0);
+        assert_eq!(approximation.integral_total_share(), 32.0);
+        assert!(approx_eq!(
+            f64,
+            approximation.ratio(),
+            14.0 / 15.12,
+            epsilon = 1e-9
+        ));
+        assert!(approximation
+            .to_string()
+            .ends_with("ratio 0.9259\ntotal_share: fractional 30.2400, integral 32.0000\n"));
+        assert!(Approximation::solve("ceei", &[1.0], &[vec![1.0]]).is_err());
+    }
+}
@@ -1,10 +1,346 @@
 pub use self::asset_fairness::*;
+pub use self::baselines::*;
 pub use self::ceei::*;
 pub use self::drf::*;
+pub use self::hierarchy::*;
+pub use self::integral::*;
+pub use self::nash::*;
 mod asset_fairness;
+mod baselines;
 mod ceei;
 mod drf;
+mod hierarchy;
+mod integral;
+mod nash;
+
+use crate::gurobi::{GurobiOptimizer, GurobiVar};
+use crate::instance::Instance;
+use crate::resource::ResourceKind;
 
 pub trait Algorithm {
     fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64>;
+
+    /// Returns the linear program the algorithm solves for the given
+    /// instance, without solving it. Algorithms that are not LP based return
+    /// None.
+    fn formulate(&self, _resources: &[f64], _demands: &[Vec<f64>]) -> Option<Formulation> {
+        None
+    }
+}
+
+/// Names of the algorithms accepted by from_name().
+pub const ALGORITHM_NAMES: &[&str] = &[
+    "drf",
+    "ceei",
+    "asset_fairness",
+    "integral_drf",
+    "integral_asset_fairness",
+    "nash_welfare",
+    "static_partition",
+    "per_resource_max_min",
+    "fifo",
+    "max_throughput",
+    "bottleneck_fairness",
+];
+
+/// Creates the algorithm called `name`, or None if there is no such
+/// algorithm. Asset fairness is priced so that every resource is worth the
+/// same in aggregate, i.e. the price of a unit is the inverse of its capacity.
+pub fn from_name(name: &str, resources: &[f64]) -> Option<Box<dyn Algorithm>> {
+    match name {
+        "drf" => Some(Box::new(Drf {})),
+        "ceei" => Some(Box::new(Ceei {})),
+        "asset_fairness" => Some(Box::new(AssetFairness::new(
+            resources.iter().map(|r| 1.0 / r).collect(),
+        ))),
+        "integral_drf" => Some(Box::new(IntegralDrf {})),
+        "integral_asset_fairness" => Some(Box::new(IntegralAssetFairness::new(
+            resources.iter().map(|r| 1.0 / r).collect(),
+        ))),
+        "nash_welfare" => Some(Box::new(NashWelfare {})),
+        "static_partition" => Some(Box::new(StaticPartition {})),
+        "per_resource_max_min" => Some(Box::new(PerResourceMaxMin {})),
+        "fifo" => Some(Box::new(Fifo {})),
+        "max_throughput" => Some(Box::new(MaxThroughput {})),
+        "bottleneck_fairness" => Some(Box::new(BottleneckFairness {})),
+        _ => None,
+    }
+}
+
+/// Solves `instance` with `alg`, writing its linear program to `model` and,
+/// if given, the solution to `solution`, so odd allocations can be inspected
+/// offline. Returns the coefficient of each demand. Fails if `alg` is not LP
+/// based.
+pub fn export(
+    alg: &dyn Algorithm,
+    instance: &Instance,
+    model: &str,
+    solution: Option<&str>,
+) -> Result<Vec<f64>, String> {
+    let mut formulation = alg
+        .formulate(&instance.capacity, &instance.demands)
+        .ok_or_else(|| "algorithm is not LP based".to_string())?;
+    formulation.optimizer.write(model);
+    let coeffs = formulation.solve();
+    if let Some(solution) = solution {
+        formulation.optimizer.write(solution);
+    }
+    Ok(coeffs)
+}
+
+/// Allocates resources of the given `kinds` to `demands`, returning the
+/// coefficient of each demand and whether it got its space.
+///
+/// Space resources are granted all-or-nothing: demands `holding` their space
+/// keep it, even if the capacity has since shrunk, and the others get theirs
+/// in order while it fits. Rate resources are then shared by `alg` among the
+/// demands that got their space, where `alg` only sees the rate resources.
+/// Demands without their space, or needing a rate resource with no capacity
+/// left, get nothing. Demands needing no rate resource get all of it.
+/// Memory resources are held from arrival, so they are left out.
+pub fn allocate_typed(
+    alg: &dyn Algorithm,
+    resources: &[f64],
+    kinds: &[ResourceKind],
+    demands: &[Vec<f64>],
+    holding: &[bool],
+) -> (Vec<f64>, Vec<bool>) {
+    allocate_typed_with(
+        |resources, demands, _| alg.allocate(resources, demands),
+        resources,
+        kinds,
+        demands,
+        holding,
+    )
+}
+
+/// Same as allocate_typed(), but the rate resources are shared by `share`,
+/// which is also given the index in `demands` of every demand it shares
+/// among.
+pub fn allocate_typed_with<F>(
+    share: F,
+    resources: &[f64],
+    kinds: &[ResourceKind],
+    demands: &[Vec<f64>],
+    holding: &[bool],
+) -> (Vec<f64>, Vec<bool>)
+where
+    F: FnOnce(&[f64], &[Vec<f64>], &[usize]) -> Vec<f64>,
+{
+    assert!(resources.len() == kinds.len() && demands.len() == holding.len());
+    let rates: Vec<usize> = (0..kinds.len())
+        .filter(|j| kinds[*j] == ResourceKind::Rate)
+        .collect();
+    let spaces: Vec<usize> = (0..kinds.len())
+        .filter(|j| kinds[*j] == ResourceKind::Space)
+        .collect();
+
+    let mut used: Vec<f64> = spaces
+        .iter()
+        .map(|j| {
+            (0..demands.len())
+                .filter(|i| holding[*i])
+                .map(|i| demands[i][*j])
+                .sum()
+        })
+        .collect();
+    let mut granted = holding.to_vec();
+    for (i, demand) in demands.iter().enumerate() {
+        if granted[i] {
+            continue;
+        }
+        let fits = spaces
+            .iter()
+            .zip(&used)
+            .all(|(j, used)| used + demand[*j] <= resources[*j]);
+        if fits {
+            for (j, used) in spaces.iter().zip(used.iter_mut()) {
+                *used += demand[*j];
+            }
+            granted[i] = true;
+        }
+    }
+
+    let mut coeffs = vec![0.0; demands.len()];
+    let mut sharing: Vec<usize> = Vec::new();
+    for (i, demand) in demands.iter().enumerate() {
+        if !granted[i]
+            || rates
+                .iter()
+                .any(|j| demand[*j] > 0.0 && resources[*j] <= 0.0)
+        {
+            continue;
+        }
+        if rates.iter().all(|j| demand[*j] <= 0.0) {
+            coeffs[i] = 1.0;
+        } else {
+            sharing.push(i);
+        }
+    }
+    if !sharing.is_empty() {
+        // None of the demands shared among needs a rate resource with no
+        // capacity left, so it is shown to `share` as 1 rather than 0,
+        // leaving nothing for algorithms to divide by 0.
+        let rate_resources: Vec<f64> = rates
+            .iter()
+            .map(|j| {
+                if resources[*j] > 0.0 {
+                    resources[*j]
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+        let rate_demands: Vec<Vec<f64>> = sharing
+            .iter()
+            .map(|i| rates.iter().map(|j| demands[*i][*j]).collect())
+            .collect();
+        let shared = share(&rate_resources, &rate_demands, &sharing);
+        assert!(shared.len() == sharing.len());
+        for (i, coeff) in sharing.into_iter().zip(shared) {
+            coeffs[i] = coeff;
+        }
+    }
+    (coeffs, granted)
+}
+
+/// An allocation problem as a linear program, with one variable per demand,
+/// named `tenant_<i>`. The solution of each variable is the fraction of its
+/// demand the tenant gets.
+pub struct Formulation {
+    pub optimizer: GurobiOptimizer,
+    pub coeffs: Vec<GurobiVar>,
+}
+
+impl Formulation {
+    pub fn new(name: &str, num_demands: usize) -> Formulation {
+        let mut optimizer = GurobiOptimizer::new(name);
+        let coeffs: Vec<GurobiVar> = (0..num_demands)
+            .map(|i| optimizer.add_named_var('C', true, &format!("tenant_{}", i)))
+            .collect();
+        Formulation { optimizer, coeffs }
+    }
+
+    /// Same as new(), but every demand gets a whole number of units, and the
+    /// variable of demand i is weighted by `objective[i]`.
+    pub fn integral(name: &str, objective: &[f64]) -> Formulation {
+        let mut optimizer = GurobiOptimizer::new(name);
+        let coeffs: Vec<GurobiVar> = objective
+            .iter()
+            .enumerate()
+            .map(|(i, obj)| optimizer.add_weighted_var('I', *obj, &format!("tenant_{}", i)))
+            .collect();
+        Formulation { optimizer, coeffs }
+    }
+
+    /// Adds a constraint named `resource_<j>` for each type of resources, so
+    /// that the allocated demands fit in the capacity.
+    pub fn add_capacity_constraints(&mut self, resources: &[f64], demands: &[Vec<f64>]) {
+        for (j, resource) in resources.iter().enumerate() {
+            self.optimizer.add_named_constraint(
+                &self.coeffs,
+                &demands.iter().map(|demand| demand[j]).collect::<Vec<f64>>(),
+                '<',
+                *resource,
+                &format!("resource_{}", j),
+            );
+        }
+    }
+
+    /// Solves the linear program and returns the coefficient of each demand.
+    pub fn solve(&mut self) -> Vec<f64> {
+        self.optimizer.optimize("max");
+
+        self.coeffs
+            .iter()
+            .map(|var| *self.optimizer.solutions.get(var).unwrap())
+            .collect()
+    }
+}
+
+/// Gives every demand the same coefficient, as much as fits but at most the
+/// one it holds. Stands in for a real algorithm in tests that do not depend
+/// on how it allocates.
+#[cfg(test)]
+pub(crate) struct EqualScale(pub f64);
+
+#[cfg(test)]
+impl Algorithm for EqualScale {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        let scale = resources
+            .iter()
+            .enumerate()
+            .map(|(j, cap)| cap / demands.iter().map(|d| d[j]).sum::<f64>())
+            .fold(self.0, f64::min);
+        vec![scale; demands.len()]
+    }
+}
+
+/// Asserts that `alloc` is `expected_alloc`, give or take 0.01.
+#[cfg(test)]
+pub(crate) fn assert_alloc(alloc: &[f64], expected_alloc: &[f64]) {
+    assert_eq!(alloc.len(), expected_alloc.len());
+    for i in 0..alloc.len() {
+        assert!(
+            float_cmp::approx_eq!(f64, alloc[i], expected_alloc[i], epsilon = 0.01),
+            "{} != {}",
+            alloc[i],
+            expected_alloc[i]
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn typed() {
+        let kinds = [ResourceKind::Rate, ResourceKind::Space];
+        let demands = vec![
+            vec![4.0, 6.0],
+            vec![4.0, 6.0],
+            vec![4.0, 3.0],
+            vec![0.0, 1.0],
+        ];
+        // The second demand does not fit next to the held first one.
+        let (coeffs, granted) = allocate_typed(
+            &EqualScale(1.0),
+            &[6.0, 10.0],
+            &kinds,
+            &demands,
+            &[true, false, false, false],
+        );
+        assert_eq!(granted, [true, false, true, true]);
+        assert_eq!(coeffs, [0.75, 0.0, 0.75, 1.0]);
+
+        // Held space is kept when the capacity shrinks, and nobody gets a
+        // rate resource that is gone.
+        let (coeffs, granted) = allocate_typed(
+            &EqualScale(1.0),
+            &[0.0, 5.0],
+            &kinds,
+            &demands,
+            &[true, false, false, false],
+        );
+        assert_eq!(granted, [true, false, false, false]);
+        assert_eq!(coeffs, [0.0, 0.0, 0.0, 0.0]);
+
+        // A rate resource that is gone is never shown with no capacity.
+        struct Positive;
+        impl Algorithm for Positive {
+            fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+                assert!(resources.iter().all(|r| *r > 0.0), "{:?}", resources);
+                EqualScale(1.0).allocate(resources, demands)
+            }
+        }
+        let (coeffs, _) = allocate_typed(
+            &Positive,
+            &[0.0, 4.0],
+            &[ResourceKind::Rate, ResourceKind::Rate],
+            &[vec![1.0, 2.0], vec![0.0, 2.0], vec![0.0, 4.0]],
+            &[false, false, false],
+        );
+        assert_eq!(coeffs, [0.0, 2.0 / 3.0, 2.0 / 3.0]);
+    }
 }
@@ -0,0 +1,148 @@
+use super::baselines::fit;
+use super::{Algorithm, Formulation};
+use crate::gurobi::{GurobiOptimizer, GurobiVar};
+
+/// Number of halvings of the initial tangent points, down to 2^-20 of what a
+/// tenant could get alone.
+const NUM_TANGENTS: i32 = 20;
+
+/// Number of times the approximation is refined with tangents at the last
+/// solution.
+const NUM_REFINEMENTS: usize = 10;
+
+/// Nash social welfare: maximizes the product of the numbers of units every
+/// tenant gets, i.e. the sum of their logarithms.
+///
+/// Gurobi only solves linear programs here, so the logarithm of each tenant's
+/// utility is bounded by tangents, at first on a geometric grid, then also at
+/// every solution found, which converges on the optimum. Utilities are
+/// normalized by what a tenant could get alone, which does not change the
+/// optimum, so that the same grid fits every tenant.
+pub struct NashWelfare {}
+
+/// The linear program, with the variable bounding each tenant's logarithm,
+/// None for tenants that can get nothing.
+struct LogApproximation {
+    formulation: Formulation,
+    logs: Vec<Option<GurobiVar>>,
+    alone: Vec<f64>,
+    num_tangents: usize,
+}
+
+impl LogApproximation {
+    /// Adds the tangent of the logarithm at `y` to the bound of tenant `i`.
+    /// Logarithms are shifted so that every tangent is non-negative for
+    /// non-negative utilities, as Gurobi variables are.
+    fn add_tangent(&mut self, i: usize, y: f64) {
+        let log = match self.logs[i] {
+            Some(log) => log,
+            None => return,
+        };
+        let shift = NUM_TANGENTS as f64 * 2f64.ln() + 1.0;
+        // log_i <= shift + ln(y) + (x_i / alone_i - y) / y
+        self.formulation.optimizer.add_named_constraint(
+            &[log, self.formulation.coeffs[i]],
+            &[1.0, -1.0 / (self.alone[i] * y)],
+            '<',
+            shift + y.ln() - 1.0,
+            &format!("tangent_{}_{}", i, self.num_tangents),
+        );
+        self.num_tangents += 1;
+    }
+}
+
+impl NashWelfare {
+    fn approximate(&self, resources: &[f64], demands: &[Vec<f64>]) -> LogApproximation {
+        for demand in demands {
+            assert!(demand.len() == resources.len());
+        }
+        let mut optimizer = GurobiOptimizer::new("nash_welfare");
+        let coeffs: Vec<GurobiVar> = (0..demands.len())
+            .map(|i| optimizer.add_named_var('C', false, &format!("tenant_{}", i)))
+            .collect();
+        let alone: Vec<f64> = demands
+            .iter()
+            .map(|demand| fit(resources, demand))
+            .collect();
+        let logs: Vec<Option<GurobiVar>> = alone
+            .iter()
+            .enumerate()
+            .map(|(i, alone)| {
+                if *alone > 0.0 {
+                    Some(optimizer.add_named_var('C', true, &format!("log_{}", i)))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        let mut approximation = LogApproximation {
+            formulation: Formulation { optimizer, coeffs },
+            logs,
+            alone,
+            num_tangents: 0,
+        };
+        approximation
+            .formulation
+            .add_capacity_constraints(resources, demands);
+        for i in 0..demands.len() {
+            for k in 0..=NUM_TANGENTS {
+                approximation.add_tangent(i, 2f64.powi(-k));
+            }
+        }
+        approximation
+    }
+}
+
+impl Algorithm for NashWelfare {
+    fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+        let mut approximation = self.approximate(resources, demands);
+        let mut coeffs = approximation.formulation.solve();
+        for _ in 0..NUM_REFINEMENTS {
+            for (i, coeff) in coeffs.iter().enumerate() {
+                if *coeff > 0.0 && approximation.alone[i] > 0.0 {
+                    let y = f64::min(coeff / approximation.alone[i], 1.0);
+                    approximation.add_tangent(i, y);
+                }
+            }
+            coeffs = approximation.formulation.solve();
+        }
+        coeffs
+    }
+
+    /// The initial approximation, before any refinement.
+    fn formulate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Option<Formulation> {
+        Some(self.approximate(resources, demands).formulation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::{assert_alloc, Ceei};
+
+    #[test]
+    fn simple_example() {
+        // With two tenants both programs end up at the same vertex.
+        let resources = vec![9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        let nash = NashWelfare {}.allocate(&resources, &demands);
+        assert_alloc(&nash, &[4.09, 1.63]);
+        assert_alloc(&nash, &Ceei {}.allocate(&resources, &demands));
+    }
+
+    #[test]
+    fn multiple_reqs() {
+        // Ceei starves the third tenant, while the product of utilities is
+        // zero unless everybody gets something: only the first resource is
+        // binding and each tenant gets a third of it.
+        let resources = vec![9.0, 18.0, 15.0];
+        let demands = vec![
+            vec![1.0, 4.0, 2.0],
+            vec![3.0, 1.0, 3.0],
+            vec![4.0, 2.0, 1.0],
+        ];
+        let nash = NashWelfare {}.allocate(&resources, &demands);
+        assert_alloc(&nash, &[3.0, 1.0, 0.75]);
+        assert!(nash[2] > Ceei {}.allocate(&resources, &demands)[2]);
+    }
+}
@@ -1,5 +1,6 @@
-pub use std::os::raw::{c_char, c_double, c_int, c_void};
+pub use std::os::raw::{c_char, c_double, c_int};
 
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::convert::TryInto;
 use std::ffi::CStr;
@@ -103,11 +104,18 @@ extern "C" {
 
     pub fn GRBsetintattr(model: *mut GRBmodel, attrname: *const c_char, value: c_int) -> c_int;
 
-    #[allow(dead_code)]
     // Write to file
     pub fn GRBwrite(model: *mut GRBmodel, filename: *const c_char) -> c_int;
 }
 
+/// A Gurobi model together with the environment it was created in.
+///
+/// Gurobi environments are not thread-safe, and a model may only be used
+/// from one thread at a time and never concurrently with other models of the
+/// same environment. Every GurobiOptimizer therefore loads its own
+/// environment and never hands out the raw pointers, so the optimizer can be
+/// moved to another thread (it is Send) but not shared between threads (it is
+/// not Sync). To allocate in parallel, create one optimizer per thread.
 pub struct GurobiOptimizer {
     env: *mut GRBenv,
     model: *mut GRBmodel,
@@ -116,6 +124,23 @@ pub struct GurobiOptimizer {
     pub solutions: HashMap<GurobiVar, f64>,
 }
 
+thread_local! {
+    /// Whether optimizers created on this thread log, see set_logging().
+    static LOGGING: Cell<bool> = const { Cell::new(true) };
+}
+
+/// Turns logging to `<name>.log` on or off for the optimizers created on the
+/// current thread from now on. Threads running optimizers side by side turn
+/// it off, as optimizers of the same name would write to the same file.
+pub fn set_logging(enabled: bool) {
+    LOGGING.with(|logging| logging.set(enabled));
+}
+
+// Safe because the optimizer exclusively owns both its environment and its
+// model (see the comment on GurobiOptimizer), so moving it moves everything
+// Gurobi may touch along with it.
+unsafe impl Send for GurobiOptimizer {}
+
 impl GurobiOptimizer {
     pub fn new(name: &str) -> GurobiOptimizer {
         let mut optimizer = GurobiOptimizer {
@@ -125,7 +150,13 @@ impl GurobiOptimizer {
             vars: Vec::new(),
             solutions: HashMap::new(),
         };
-        let log_file_c_str = CString::new(name.to_owned() + ".log").expect("CString::new failed");
+        // Gurobi does not log given an empty file name.
+        let log_file = if LOGGING.with(Cell::get) {
+            name.to_owned() + ".log"
+        } else {
+            String::new()
+        };
+        let log_file_c_str = CString::new(log_file).expect("CString::new failed");
         let log_file_c_ptr = log_file_c_str.as_ptr();
         let model_name_c_str = CString::new(name).expect("CString::new failed");
         let model_name_c_ptr = model_name_c_str.as_ptr();
@@ -151,24 +182,41 @@ impl GurobiOptimizer {
         }
         optimizer
     }
+    #[allow(dead_code)]
     pub fn add_var(&mut self, var_type: char, is_objective: bool) -> GurobiVar {
+        self.add_var_with_name(var_type, is_objective as i8 as f64, None)
+    }
+    /// Same as add_var(), but the variable shows up under `name` in model
+    /// files written by write().
+    pub fn add_named_var(&mut self, var_type: char, is_objective: bool, name: &str) -> GurobiVar {
+        self.add_var_with_name(var_type, is_objective as i8 as f64, Some(name))
+    }
+    /// Same as add_named_var(), but the variable is weighted by `obj` in the
+    /// objective.
+    pub fn add_weighted_var(&mut self, var_type: char, obj: f64, name: &str) -> GurobiVar {
+        self.add_var_with_name(var_type, obj, Some(name))
+    }
+    fn add_var_with_name(&mut self, var_type: char, obj: f64, name: Option<&str>) -> GurobiVar {
         assert!(
             ['C', 'B', 'I'].contains(&var_type),
             "var_type must be C (real), B (binary), or I (integer)"
         );
+        let name_c_str = name.map(|name| CString::new(name).expect("CString::new failed"));
+        let name_c_ptr = name_c_str
+            .as_ref()
+            .map_or(ptr::null(), |name| name.as_ptr());
         unsafe {
-            let coeff = is_objective as i8 as f64;
             gurobi_try!(
                 GRBaddvar(
                     self.model,
                     0,
                     ptr::null_mut(),
                     ptr::null_mut(),
-                    coeff,
+                    obj,
                     0.0,
                     1e100,
                     var_type as i8,
-                    ptr::null()
+                    name_c_ptr
                 ),
                 self.env
             );
@@ -177,16 +225,43 @@ impl GurobiOptimizer {
         self.var_index += 1;
         self.var_index - 1 // return newly created index.
     }
+    #[allow(dead_code)]
     pub fn add_constraint(
         &mut self,
         lhs_vars: &[GurobiVar],
         lhs_coeffs: &[f64],
         sense: char,
         rhs: f64,
+    ) {
+        self.add_constraint_with_name(lhs_vars, lhs_coeffs, sense, rhs, None);
+    }
+    /// Same as add_constraint(), but the constraint shows up under `name` in
+    /// model files written by write().
+    pub fn add_named_constraint(
+        &mut self,
+        lhs_vars: &[GurobiVar],
+        lhs_coeffs: &[f64],
+        sense: char,
+        rhs: f64,
+        name: &str,
+    ) {
+        self.add_constraint_with_name(lhs_vars, lhs_coeffs, sense, rhs, Some(name));
+    }
+    fn add_constraint_with_name(
+        &mut self,
+        lhs_vars: &[GurobiVar],
+        lhs_coeffs: &[f64],
+        sense: char,
+        rhs: f64,
+        name: Option<&str>,
     ) {
         let sense = sense as c_char;
         assert!(['<' as c_char, '>' as c_char, '=' as c_char].contains(&sense));
         assert!(lhs_vars.len() == lhs_coeffs.len());
+        let name_c_str = name.map(|name| CString::new(name).expect("CString::new failed"));
+        let name_c_ptr = name_c_str
+            .as_ref()
+            .map_or(ptr::null(), |name| name.as_ptr());
         unsafe {
             gurobi_try!(
                 GRBaddconstr(
@@ -196,7 +271,7 @@ impl GurobiOptimizer {
                     lhs_coeffs.as_ptr(),
                     sense,
                     rhs,
-                    ptr::null()
+                    name_c_ptr
                 ),
                 self.env
             );
@@ -219,6 +294,15 @@ impl GurobiOptimizer {
                 .insert(var.to_owned(), self.get_solution(var));
         }
     }
+    /// Writes the model to `filename`. Gurobi picks the format from the
+    /// extension: `.lp` and `.mps` dump the model itself, while `.sol` dumps
+    /// the solution and is only valid after optimize().
+    pub fn write(&self, filename: &str) {
+        let filename_c_str = CString::new(filename).expect("CString::new failed");
+        unsafe {
+            gurobi_try!(GRBwrite(self.model, filename_c_str.as_ptr()), self.env);
+        }
+    }
     fn get_solution(&self, var: GurobiVar) -> f64 {
         let x_str = CString::new("X").expect("CString::new failed");
         let mut x: f64 = 0.0;
@@ -238,11 +322,11 @@ impl Drop for GurobiOptimizer {
             GRBfreemodel(self.model);
             GRBfreeenv(self.env);
         }
-        println!("Dropping model and environment in GurobiOptimizer's destructor.\n");
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec)]
 mod tests {
     use super::*;
     #[test]
@@ -291,4 +375,51 @@ mod tests {
         assert!(*optimizer.solutions.get(&x).unwrap() == 0.0);
         assert!(*optimizer.solutions.get(&y).unwrap() == 12.0);
     }
+
+    #[test]
+    fn test_weighted() {
+        let mut optimizer = GurobiOptimizer::new("weighted");
+        let x = optimizer.add_weighted_var('I', 2.0, "x");
+        let y = optimizer.add_weighted_var('I', 1.0, "y");
+        optimizer.add_constraint(&[x, y], &[1.0, 1.0], '<', 3.0);
+        optimizer.add_constraint(&[x], &[1.0], '<', 1.0);
+        optimizer.optimize("max");
+        assert!(*optimizer.solutions.get(&x).unwrap() == 1.0);
+        assert!(*optimizer.solutions.get(&y).unwrap() == 2.0);
+    }
+
+    #[test]
+    fn test_write_lp() {
+        let mut optimizer = GurobiOptimizer::new("write_lp");
+        let x = optimizer.add_named_var('C', true, "tenant_0");
+        let y = optimizer.add_named_var('C', true, "tenant_1");
+        optimizer.add_named_constraint(&[x, y], &[1.0, 2.0], '<', 4.0, "resource_0");
+        optimizer.optimize("max");
+
+        let path = std::env::temp_dir().join("denarii_test_write_lp.lp");
+        let path = path.to_str().unwrap();
+        optimizer.write(path);
+        let lp = std::fs::read_to_string(path).unwrap();
+        assert!(lp.contains("tenant_0"));
+        assert!(lp.contains("tenant_1"));
+        assert!(lp.contains("resource_0"));
+    }
+
+    #[test]
+    fn test_parallel() {
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                std::thread::spawn(move || {
+                    let mut optimizer = GurobiOptimizer::new(&format!("parallel_{}", i));
+                    let x = optimizer.add_var('C', true);
+                    optimizer.add_constraint(&[x], &[1.0], '<', i as f64);
+                    optimizer.optimize("max");
+                    *optimizer.solutions.get(&x).unwrap()
+                })
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            assert!(handle.join().unwrap() == i as f64);
+        }
+    }
 }
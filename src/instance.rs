@@ -0,0 +1,86 @@
+use std::fs;
+use std::io;
+
+/// A single allocation problem: the capacity of each resource and the demand
+/// vector of each tenant.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Instance {
+    pub capacity: Vec<f64>,
+    pub demands: Vec<Vec<f64>>,
+}
+
+impl Instance {
+    /// Reads an instance from a text file. Every non-empty line that does not
+    /// start with `#` is a whitespace separated list of numbers. The first
+    /// such line holds the capacities, and every following line the demand of
+    /// one tenant, e.g.
+    ///
+    /// ```text
+    /// # capacity
+    /// 9 18
+    /// # demands
+    /// 1 4
+    /// 3 1
+    /// ```
+    pub fn from_file(path: &str) -> io::Result<Instance> {
+        Instance::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(text: &str) -> io::Result<Instance> {
+        let mut rows: Vec<Vec<f64>> = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let row = line
+                .split_whitespace()
+                .map(|x| x.parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| invalid_data(format!("line {}: {}", lineno + 1, e)))?;
+            rows.push(row);
+        }
+
+        if rows.is_empty() {
+            return Err(invalid_data("missing capacities".to_string()));
+        }
+        let capacity = rows.remove(0);
+        for (i, demand) in rows.iter().enumerate() {
+            if demand.len() != capacity.len() {
+                return Err(invalid_data(format!(
+                    "demand {} has {} resources, expected {}",
+                    i,
+                    demand.len(),
+                    capacity.len()
+                )));
+            }
+        }
+        Ok(Instance {
+            capacity,
+            demands: rows,
+        })
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let instance = Instance::parse("# capacity\n9 18\n\n# demands\n1 4\n3 1\n").unwrap();
+        assert_eq!(instance.capacity, [9.0, 18.0]);
+        assert_eq!(instance.demands, [[1.0, 4.0], [3.0, 1.0]]);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Instance::parse("# nothing here\n").is_err());
+        assert!(Instance::parse("9 18\n1 x\n").is_err());
+        assert!(Instance::parse("9 18\n1 4 2\n").is_err());
+    }
+}
@@ -0,0 +1,8 @@
+pub mod algorithms;
+pub mod gurobi;
+pub mod instance;
+pub mod manipulation;
+pub mod resource;
+pub mod simulator;
+pub mod stats;
+pub mod sweep;
@@ -1,15 +1,17 @@
-mod algorithms;
-mod gurobi;
-mod simulator;
-
 extern crate clap;
 
-use algorithms::Algorithm;
-use clap::{App, Arg};
-use rand::distributions::{Bernoulli, Distribution};
-use rand::rngs::StdRng;
-use rand::{Rng, SeedableRng};
-use simulator::Packet;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use denarii::algorithms::{self, Approximation};
+use denarii::instance::Instance;
+use denarii::manipulation;
+use denarii::resource::Resource;
+use denarii::simulator;
+use denarii::simulator::{
+    DemandConfig, DropPolicy, Engine, Execution, Placement, QueueConfig, ReallocationConfig,
+    Scenario, SimConfig, SlotPolicy, TenantConfig,
+};
+use denarii::sweep::SweepConfig;
+use std::io;
 
 fn main() {
     let matches = App::new("Denarii")
@@ -30,103 +32,684 @@ fn main() {
                 .default_value("1")
                 .help("Random seed"),
         )
+        .arg(
+            Arg::with_name("algorithm")
+                .short("a")
+                .long("algorithm")
+                .possible_values(algorithms::ALGORITHM_NAMES)
+                .default_value("drf")
+                .help("Allocation algorithm to run."),
+        )
+        .arg(
+            Arg::with_name("arrival_rate")
+                .short("r")
+                .long("arrival-rate")
+                .default_value("0.3")
+                .help("Probability that a packet arrives in a tick, unless --arrivals is given."),
+        )
+        .arg(
+            Arg::with_name("arrivals")
+                .long("arrivals")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Arrival process of a tenant, repeated once per tenant: bernoulli:p, \
+                     poisson:rate, onoff:on_rate,off_rate,p_on_off,p_off_on, \
+                     periodic:period[,offset] or diurnal:mean,amplitude,period.",
+                ),
+        )
+        .arg(
+            Arg::with_name("service_time")
+                .long("service-time")
+                .default_value(simulator::DEFAULT_SERVICE_TIME)
+                .help(
+                    "Service time distribution of every tenant: randint:low,high, \
+                     uniform:low,high, const:value, exp:mean, lognormal:mu,sigma, \
+                     pareto:scale,shape or empirical:value=weight,...",
+                ),
+        )
+        .arg(
+            Arg::with_name("demand")
+                .long("demand")
+                .default_value(simulator::DEFAULT_DEMAND)
+                .help(
+                    "Distribution each resource demand of every tenant is drawn from, \
+                     see --service-time.",
+                ),
+        )
+        .arg(
+            Arg::with_name("size")
+                .long("size")
+                .default_value(simulator::DEFAULT_SIZE)
+                .help(
+                    "Distribution of the packet size in bytes, which is what packets hold of \
+                     every memory resource, see --service-time.",
+                ),
+        )
+        .arg(
+            Arg::with_name("capacity")
+                .short("c")
+                .long("capacity")
+                .default_value("10,20")
+                .help("Comma separated capacity of each resource."),
+        )
+        .arg(
+            Arg::with_name("device")
+                .long("device")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Comma separated capacity of one device of a cluster, in place of \
+                     --capacity. May be repeated.",
+                ),
+        )
+        .arg(
+            Arg::with_name("placement")
+                .long("placement")
+                .takes_value(true)
+                .help(
+                    "Which device of a cluster packets go to: first_fit, best_fit or drfh, \
+                     followed by :tenant to place tenants rather than packets. first_fit by \
+                     default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("resources")
+                .long("resources")
+                .use_delimiter(true)
+                .help(
+                    "Comma separated name:kind of each resource, where kind is rate, space \
+                     or memory. Space is held in full for a packet's lifetime, memory from \
+                     its arrival, waiting included, in bytes of its --size. All resources \
+                     are rates named r0, r1, ... if omitted.",
+                ),
+        )
+        .arg(
+            Arg::with_name("capacity_schedule")
+                .long("capacity-schedule")
+                .multiple(true)
+                .number_of_values(1)
+                .help(
+                    "Changes capacity over time: step:t,resource,capacity, \
+                     maintenance:resource,period,duration,capacity or \
+                     failure:resource,units,p_fail,p_recover. May be repeated.",
+                ),
+        )
+        .arg(
+            Arg::with_name("scenario")
+                .long("scenario")
+                .takes_value(true)
+                .conflicts_with_all(&[
+                    "arrivals",
+                    "arrival_rate",
+                    "service_time",
+                    "demand",
+                    "size",
+                ])
+                .help("JSON file with the workload of each tenant and optionally the capacity."),
+        )
+        .arg(
+            Arg::with_name("queue_limit")
+                .long("queue-limit")
+                .takes_value(true)
+                .help("Maximum number of packets held by the device, unlimited by default."),
+        )
+        .arg(
+            Arg::with_name("tenant_queue_limit")
+                .long("tenant-queue-limit")
+                .takes_value(true)
+                .help("Maximum number of packets held per tenant, unlimited by default."),
+        )
+        .arg(
+            Arg::with_name("drop_policy")
+                .long("drop-policy")
+                .default_value("tail")
+                .help(
+                    "What to drop when the device is busy: tail, red:min_th,max_th,max_p or \
+                     quota (an equal share of --queue-limit per tenant).",
+                ),
+        )
+        .arg(
+            Arg::with_name("no_admission_control")
+                .long("no-admission-control")
+                .help("Admit packets that demand more of a resource than its capacity."),
+        )
+        .arg(
+            Arg::with_name("slots")
+                .long("slots")
+                .takes_value(true)
+                .help(
+                    "Number of packets served at once, unlimited by default. Other packets \
+                     wait for a slot and get no resources.",
+                ),
+        )
+        .arg(
+            Arg::with_name("slot_policy")
+                .long("slot-policy")
+                .possible_values(&["fifo", "sjf", "rr"])
+                .default_value("fifo")
+                .help(
+                    "Which waiting packet gets a free slot: first arrived, shortest service \
+                     time, or round robin over tenants.",
+                ),
+        )
+        .arg(
+            Arg::with_name("engine")
+                .long("engine")
+                .possible_values(&["tick", "event"])
+                .default_value("tick")
+                .help(
+                    "Step every packet each tick, or jump between arrival and completion \
+                     events with exact completion times.",
+                ),
+        )
+        .arg(
+            Arg::with_name("execution")
+                .long("execution")
+                .default_value("fluid")
+                .help(
+                    "Share the resources among packets in service with the allocation algorithm \
+                     (fluid), start packets one by one with all they request, in the order of \
+                     a packet scheduler: drfq, mr3, wfq:<resource>, priority (by tenant) or \
+                     priority:class (by traffic class, highest first), or pass packets \
+                     through the resources as stages of a pipeline, with --slots packets per \
+                     stage and optionally a buffer in front of each: pipeline[:<buffer>].",
+                ),
+        )
+        .arg(
+            Arg::with_name("trace")
+                .long("trace")
+                .takes_value(true)
+                .conflicts_with_all(&["arrivals", "arrival_rate", "scenario"])
+                .help("Replays packets from a .csv or .json trace instead of generating them."),
+        )
+        .arg(
+            Arg::with_name("record")
+                .long("record")
+                .takes_value(true)
+                .help("Records every arriving packet to a .csv or .json trace."),
+        )
+        .arg(
+            Arg::with_name("reallocation")
+                .long("reallocation")
+                .default_value("")
+                .help(
+                    "Cost and pace of allocation changes, as comma separated settings: \
+                     delay=<time> for new allocations to take effect, loss=<fraction> of \
+                     their allocation packets lose until then, interval=<time> at least \
+                     between allocations and hysteresis=<fraction> of its demand some \
+                     packet's allocation must change by. All off by default.",
+                ),
+        )
+        .arg(
+            Arg::with_name("tick_duration")
+                .long("tick-duration")
+                .default_value("1e-6")
+                .help("Length of a tick in seconds, which throughput is reported against."),
+        )
+        .arg(
+            Arg::with_name("occupancy")
+                .long("occupancy")
+                .takes_value(true)
+                .help(
+                    "Writes the bytes of the packets waiting and in service on every device \
+                     in every tick to a .csv file.",
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Solves a single allocation instance and writes the model and solution.")
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .takes_value(true)
+                        .required(true)
+                        .help("File with capacities on the first line and one demand per line."),
+                )
+                .arg(
+                    Arg::with_name("algorithm")
+                        .short("a")
+                        .long("algorithm")
+                        .possible_values(algorithms::ALGORITHM_NAMES)
+                        .default_value("drf")
+                        .help("Allocation algorithm to run."),
+                )
+                .arg(
+                    Arg::with_name("model")
+                        .short("m")
+                        .long("model")
+                        .default_value("model.lp")
+                        .help("Where to write the model, either .lp or .mps."),
+                )
+                .arg(
+                    Arg::with_name("solution")
+                        .long("solution")
+                        .takes_value(true)
+                        .help("Where to write the solution, a .sol file."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("integral")
+                .about(
+                    "Solves a single allocation instance in whole units and reports how close \
+                     it comes to the fractional allocation.",
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .takes_value(true)
+                        .required(true)
+                        .help("File with capacities on the first line and one demand per line."),
+                )
+                .arg(
+                    Arg::with_name("algorithm")
+                        .short("a")
+                        .long("algorithm")
+                        .possible_values(algorithms::INTEGRAL_NAMES)
+                        .default_value("drf")
+                        .help("Allocation algorithm to compare with its integral variant."),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("manipulate")
+                .about(
+                    "Searches a single allocation instance for demand misreports that get a \
+                     tenant more of what it actually needs.",
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .short("i")
+                        .long("input")
+                        .takes_value(true)
+                        .required(true)
+                        .help("File with capacities on the first line and one demand per line."),
+                )
+                .arg(
+                    Arg::with_name("algorithm")
+                        .short("a")
+                        .long("algorithm")
+                        .possible_values(algorithms::ALGORITHM_NAMES)
+                        .default_value("drf")
+                        .help("Allocation algorithm to manipulate."),
+                )
+                .arg(
+                    Arg::with_name("tenant")
+                        .long("tenant")
+                        .takes_value(true)
+                        .help("Tenant that lies, every tenant in turn if not given."),
+                )
+                .arg(
+                    Arg::with_name("samples")
+                        .long("samples")
+                        .default_value("1000")
+                        .help("Number of random misreports to try per tenant."),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .default_value("1")
+                        .help("Random seed"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("sweep")
+                .about(
+                    "Runs the simulator over a grid of algorithms, arrival rates, capacities and \
+                     seeds in parallel, and reports each configuration averaged over seeds.",
+                )
+                .arg(
+                    Arg::with_name("ticks")
+                        .short("t")
+                        .long("ticks")
+                        .default_value("100")
+                        .help("The number of ticks to run each simulation."),
+                )
+                .arg(
+                    Arg::with_name("algorithms")
+                        .short("a")
+                        .long("algorithms")
+                        .use_delimiter(true)
+                        .possible_values(algorithms::ALGORITHM_NAMES)
+                        .default_value("drf,ceei,asset_fairness")
+                        .help("Comma separated allocation algorithms to run."),
+                )
+                .arg(
+                    Arg::with_name("arrival_rates")
+                        .short("r")
+                        .long("arrival-rates")
+                        .use_delimiter(true)
+                        .help(
+                            "Comma separated Bernoulli arrival rates to run, 0.3 if neither \
+                             this nor --arrivals is given.",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("arrivals")
+                        .long("arrivals")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("Arrival process spec to run for a single tenant, may be repeated."),
+                )
+                .arg(
+                    Arg::with_name("capacities")
+                        .short("c")
+                        .long("capacity")
+                        .multiple(true)
+                        .number_of_values(1)
+                        .default_value("10,20")
+                        .help("Comma separated capacity of each resource, may be repeated."),
+                )
+                .arg(
+                    Arg::with_name("seeds")
+                        .short("s")
+                        .long("seeds")
+                        .default_value("5")
+                        .help("Number of seeds to run per algorithm, starting from 1."),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .short("j")
+                        .long("threads")
+                        .default_value("4")
+                        .help("Number of worker threads."),
+                )
+                .arg(
+                    Arg::with_name("engine")
+                        .long("engine")
+                        .possible_values(&["tick", "event"])
+                        .default_value("tick")
+                        .help("Step every packet each tick, or jump between events."),
+                )
+                .arg(
+                    Arg::with_name("execution")
+                        .long("execution")
+                        .default_value("fluid")
+                        .help(
+                            "Share resources as a fluid, or schedule packets with drfq, mr3, \
+                             wfq:<resource>, priority or priority:class.",
+                        ),
+                ),
+        )
         .get_matches();
 
-    let seed: u64 = matches.value_of("seed").unwrap().parse::<u64>().unwrap();
-    let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
-
-    let ticks = matches.value_of("ticks").unwrap().parse::<u64>().unwrap();
-
-    // Packets not allocated
-    let mut pkts: Vec<Packet> = Vec::new();
-    let mut completed: Vec<Packet> = Vec::new();
-
-    let p = 0.3;
-    // Distribution for packet arrivals.
-    let a_dist = Bernoulli::new(p).unwrap();
-    let num_resources = 2;
-
-    // TODO: Provide ways to set initial capacity of resources, 1) commandline
-    // argument, 2) randomly generate them.
-    let capacity: Vec<f64> = (0..num_resources)
-        .map(|x| ((x + 1) as f64) * 10.0)
-        .collect();
-
-    let alg = algorithms::Drf {};
-
-    let mut num_pkts = 0;
-    for t in 0..ticks {
-        let add_new_packet: bool = a_dist.sample(&mut rng);
-        // New Packet coming
-        if add_new_packet {
-            let service_time = rng.gen_range(10, 20) as f64;
-            let resource_req: Vec<f64> = (0..num_resources)
-                .map(|_| rng.gen_range(1, 11) as f64)
-                .collect();
-            println!(
-                "t:{}, service_time:{}, resource_req:{:?}",
-                t, service_time, resource_req
-            );
-
-            let p: Packet = Packet::new(num_pkts, t, service_time, resource_req);
-            num_pkts += 1;
-            pkts.push(p);
-        }
+    if let Some(matches) = matches.subcommand_matches("export") {
+        export(matches);
+        return;
+    }
 
-        // Step each packet.
-        let mut done_pkts = 0;
-        for pkt in &mut pkts {
-            let done = pkt.step();
+    if let Some(matches) = matches.subcommand_matches("integral") {
+        integral(matches);
+        return;
+    }
 
-            if done {
-                // TODO: Move instead of copy.
-                completed.push(pkt.clone());
-                done_pkts += 1;
-            }
-        }
+    if let Some(matches) = matches.subcommand_matches("manipulate") {
+        manipulate(matches);
+        return;
+    }
 
-        // Remove packets that are completed.
-        pkts.retain(|pkt| !pkt.is_completed());
+    if let Some(matches) = matches.subcommand_matches("sweep") {
+        sweep(matches);
+        return;
+    }
 
-        // Check whether a new allocation needs to happen
-        if !pkts.is_empty() && (add_new_packet || done_pkts > 0) {
-            run_allocation(&mut pkts, t, &capacity, &alg);
+    let mut capacity = parse_capacity(matches.value_of("capacity").unwrap());
+    let mut capacity_schedule: Vec<String> = Vec::new();
+    let mut resources = None;
+    let mut hierarchy = Vec::new();
+    let mut devices: Vec<Vec<f64>> = Vec::new();
+    let mut placement = None;
+    let tenants: Vec<TenantConfig> = match matches.value_of("scenario") {
+        Some(path) => {
+            let scenario = Scenario::from_file(path)
+                .unwrap_or_else(|e| panic!("failed to read scenario {}: {}", path, e));
+            resources = scenario
+                .resources()
+                .unwrap_or_else(|e| panic!("failed to read scenario {}: {}", path, e));
+            if let Some(scenario_capacity) = scenario.total_capacity() {
+                capacity = scenario_capacity;
+            }
+            devices = scenario.devices;
+            placement = scenario.placement;
+            capacity_schedule = scenario.capacity_schedule;
+            hierarchy = scenario.hierarchy;
+            scenario.tenants
+        }
+        None => {
+            let arrivals: Vec<String> = match matches.values_of("arrivals") {
+                Some(specs) => specs.map(|spec| spec.to_string()).collect(),
+                None => vec![format!(
+                    "bernoulli:{}",
+                    matches.value_of("arrival_rate").unwrap()
+                )],
+            };
+            arrivals
+                .iter()
+                .map(|spec| TenantConfig {
+                    arrivals: spec.clone(),
+                    service_time: matches.value_of("service_time").unwrap().to_string(),
+                    demand: DemandConfig::Independent(
+                        matches.value_of("demand").unwrap().to_string(),
+                    ),
+                    size: matches.value_of("size").unwrap().to_string(),
+                    class: 0,
+                    apps: Vec::new(),
+                })
+                .collect()
+        }
+    };
+    if let Some(specs) = matches.values_of("device") {
+        devices = specs.map(parse_capacity).collect();
+        if devices
+            .iter()
+            .any(|device| device.len() != devices[0].len())
+        {
+            panic!("devices have different numbers of resources");
         }
+        capacity = (0..devices[0].len())
+            .map(|j| devices.iter().map(|device| device[j]).sum())
+            .collect();
+    }
+    if let Some(spec) = matches.value_of("placement") {
+        placement = Some(spec.to_string());
+    }
+    let placement = match placement {
+        Some(spec) => Placement::from_spec(&spec).unwrap_or_else(|e| panic!("{}", e)),
+        None => Placement::default(),
+    };
+    if let Some(specs) = matches.values_of("capacity_schedule") {
+        capacity_schedule.extend(specs.map(|spec| spec.to_string()));
     }
+    if let Some(specs) = matches.values_of("resources") {
+        resources = Some(
+            specs
+                .map(|spec| Resource::from_spec(spec).unwrap_or_else(|e| panic!("{}", e)))
+                .collect(),
+        );
+    }
+    let resources = resources.unwrap_or_else(|| Resource::defaults(capacity.len()));
+    let config = SimConfig {
+        algorithm: matches.value_of("algorithm").unwrap().to_string(),
+        tenants,
+        capacity,
+        devices,
+        placement,
+        resources,
+        capacity_schedule,
+        seed: matches.value_of("seed").unwrap().parse::<u64>().unwrap(),
+        ticks: matches.value_of("ticks").unwrap().parse::<u64>().unwrap(),
+        tick_duration: matches
+            .value_of("tick_duration")
+            .unwrap()
+            .parse::<f64>()
+            .expect("invalid tick duration"),
+        trace: matches.value_of("trace").map(|path| path.to_string()),
+        record: matches.value_of("record").map(|path| path.to_string()),
+        queue: parse_queue_config(&matches),
+        slots: matches
+            .value_of("slots")
+            .map(|x| x.parse::<usize>().expect("invalid number of slots")),
+        slot_policy: SlotPolicy::from_spec(matches.value_of("slot_policy").unwrap())
+            .unwrap_or_else(|e| panic!("{}", e)),
+        engine: Engine::from_spec(matches.value_of("engine").unwrap())
+            .unwrap_or_else(|e| panic!("{}", e)),
+        execution: Execution::from_spec(matches.value_of("execution").unwrap())
+            .unwrap_or_else(|e| panic!("{}", e)),
+        hierarchy,
+        reallocation: ReallocationConfig::from_spec(matches.value_of("reallocation").unwrap())
+            .unwrap_or_else(|e| panic!("{}", e)),
+    };
+
+    let result = simulator::simulate(&config, true, matches.value_of("occupancy"))
+        .unwrap_or_else(|e| panic!("{}", e));
+    print!("{}", result);
+}
 
-    for pkt in &completed {
-        println!("{:?}", pkt);
+fn parse_queue_config(matches: &ArgMatches) -> QueueConfig {
+    let parse_limit = |name: &str| {
+        matches
+            .value_of(name)
+            .map(|x| x.parse::<usize>().expect("invalid queue limit"))
+    };
+    let policy = DropPolicy::from_spec(matches.value_of("drop_policy").unwrap())
+        .unwrap_or_else(|e| panic!("{}", e));
+    let limit = parse_limit("queue_limit");
+    assert!(
+        policy != DropPolicy::Quota || limit.is_some(),
+        "the quota drop policy needs --queue-limit"
+    );
+    QueueConfig {
+        limit,
+        tenant_limit: parse_limit("tenant_queue_limit"),
+        policy,
+        admission_control: !matches.is_present("no_admission_control"),
     }
+}
 
-    println!("{}: Total number of packets", num_pkts);
+fn parse_capacity(capacity: &str) -> Vec<f64> {
+    capacity
+        .split(',')
+        .map(|x| x.trim().parse::<f64>().expect("invalid capacity"))
+        .collect()
 }
 
-fn run_allocation(pkts: &mut [Packet], t: u64, capacity: &[f64], alg: &dyn Algorithm) {
-    let mut requests: Vec<Vec<f64>> = Vec::new();
-    for pkt in pkts.iter() {
-        requests.push(pkt.resource_req.clone());
+/// Runs every combination of algorithm, arrival process, capacity and seed,
+/// spread over worker threads, and prints one line per configuration with the
+/// mean and 95% confidence interval over seeds.
+fn sweep(matches: &ArgMatches) {
+    let num_seeds = matches.value_of("seeds").unwrap().parse::<u64>().unwrap();
+    assert!(num_seeds > 0, "need at least one seed");
+    let num_threads = matches
+        .value_of("threads")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    // Every grid point is the arrival process of a single tenant.
+    let mut arrivals: Vec<String> = Vec::new();
+    if let Some(rates) = matches.values_of("arrival_rates") {
+        for rate in rates {
+            rate.parse::<f64>().expect("invalid arrival rate");
+            arrivals.push(format!("bernoulli:{}", rate));
+        }
     }
-    println!(
-        "t: {}, capacity: {:?} requests: {:?}",
-        t, capacity, requests
-    );
-    let coeffs = alg.allocate(&capacity, &requests);
-    assert!(coeffs.len() == pkts.len());
-    for (i, pkt) in pkts.iter_mut().enumerate() {
-        let alloc = pkt.resource_req.iter().map(|x| x * coeffs[i]).collect();
-        pkt.allocate(alloc);
+    if let Some(specs) = matches.values_of("arrivals") {
+        arrivals.extend(specs.map(|spec| spec.to_string()));
+    }
+    if arrivals.is_empty() {
+        arrivals.push("bernoulli:0.3".to_string());
+    }
+    let config = SweepConfig {
+        algorithms: matches
+            .values_of("algorithms")
+            .unwrap()
+            .map(|name| name.to_string())
+            .collect(),
+        arrivals,
+        capacities: matches
+            .values_of("capacities")
+            .unwrap()
+            .map(parse_capacity)
+            .collect(),
+        seeds: num_seeds,
+        ticks: matches.value_of("ticks").unwrap().parse::<u64>().unwrap(),
+        engine: Engine::from_spec(matches.value_of("engine").unwrap())
+            .unwrap_or_else(|e| panic!("{}", e)),
+        execution: Execution::from_spec(matches.value_of("execution").unwrap())
+            .unwrap_or_else(|e| panic!("{}", e)),
+    };
+
+    let results = config.run(num_threads).unwrap_or_else(|e| panic!("{}", e));
+    config
+        .write_summary(&results, &mut io::stdout())
+        .expect("failed to write the sweep summary");
+}
+
+/// Runs a single allocation read from a file, writing out the LP model and
+/// optionally its solution so odd allocations can be inspected offline.
+fn export(matches: &ArgMatches) {
+    let input = matches.value_of("input").unwrap();
+    let instance = Instance::from_file(input)
+        .unwrap_or_else(|e| panic!("failed to read instance {}: {}", input, e));
+    let alg =
+        algorithms::from_name(matches.value_of("algorithm").unwrap(), &instance.capacity).unwrap();
+    let coeffs = algorithms::export(
+        alg.as_ref(),
+        &instance,
+        matches.value_of("model").unwrap(),
+        matches.value_of("solution"),
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+
+    for (i, coeff) in coeffs.iter().enumerate() {
+        println!("tenant_{}: {}", i, coeff);
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Compares the integral variant of an algorithm with its fractional
+/// allocation on a single instance read from a file.
+fn integral(matches: &ArgMatches) {
+    let input = matches.value_of("input").unwrap();
+    let instance = Instance::from_file(input)
+        .unwrap_or_else(|e| panic!("failed to read instance {}: {}", input, e));
+    let approximation = Approximation::solve(
+        matches.value_of("algorithm").unwrap(),
+        &instance.capacity,
+        &instance.demands,
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    print!("{}", approximation);
+}
+
+/// Searches for the most profitable demand misreport of each tenant, or of
+/// the one given, and reports it with its gain over the truth.
+fn manipulate(matches: &ArgMatches) {
+    let input = matches.value_of("input").unwrap();
+    let instance = Instance::from_file(input)
+        .unwrap_or_else(|e| panic!("failed to read instance {}: {}", input, e));
+    let alg =
+        algorithms::from_name(matches.value_of("algorithm").unwrap(), &instance.capacity).unwrap();
+    let num_samples = matches
+        .value_of("samples")
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    let seed = matches.value_of("seed").unwrap().parse::<u64>().unwrap();
+    let tenants: Vec<usize> = match matches.value_of("tenant") {
+        Some(tenant) => vec![tenant.parse::<usize>().unwrap()],
+        None => (0..instance.demands.len()).collect(),
+    };
 
-    #[test]
-    fn conatiner_ops() {
-        let mut pkts: Vec<Packet> = Vec::new();
-        pkts.push(Packet::new(1, 1, 2.0, vec![1.0, 2.0]));
-        pkts.retain(|pkt| pkt.is_scheduled());
-        assert!(pkts.is_empty());
+    let misreports = manipulation::find_misreports(
+        alg.as_ref(),
+        &instance.capacity,
+        &instance.demands,
+        &tenants,
+        num_samples,
+        seed,
+    )
+    .unwrap_or_else(|e| panic!("{}", e));
+    for misreport in misreports {
+        println!("{}", misreport);
     }
 }
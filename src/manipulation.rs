@@ -0,0 +1,242 @@
+use crate::algorithms::Algorithm;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+/// Factors the grid search scales a single reported demand by.
+const GRID_FACTORS: &[f64] = &[
+    0.1, 0.25, 0.5, 0.75, 0.9, 1.1, 1.25, 1.5, 2.0, 3.0, 5.0, 10.0,
+];
+
+/// Range of the factors random misreports scale demands by, drawn
+/// log-uniformly.
+const RANDOM_FACTORS: (f64, f64) = (0.1, 10.0);
+
+/// The best demand misreport found for a tenant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Misreport {
+    pub tenant: usize,
+    /// Demand the tenant reports in place of its true demand.
+    pub report: Vec<f64>,
+    /// Useful allocation when reporting the truth.
+    pub truthful: f64,
+    /// Useful allocation when reporting `report`.
+    pub useful: f64,
+}
+
+impl Misreport {
+    /// How much the lie gains over the truth, zero or less if no lie helps.
+    pub fn gain(&self) -> f64 {
+        self.useful - self.truthful
+    }
+}
+
+impl fmt::Display for Misreport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tenant_{}: truthful {:.4}, best report {:?}, useful {:.4}, gain {:.4}",
+            self.tenant,
+            self.truthful,
+            self.report,
+            self.useful,
+            self.gain()
+        )
+    }
+}
+
+/// Number of units of `demand` a tenant can run with what it got for
+/// reporting `report` at coefficient `coeff`: the smallest fraction of its
+/// true demand it got of any resource it needs.
+pub fn useful_allocation(demand: &[f64], report: &[f64], coeff: f64) -> f64 {
+    demand
+        .iter()
+        .zip(report)
+        .filter(|(d, _)| **d > 0.0)
+        .map(|(d, r)| coeff * r / d)
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Searches for a demand `tenant` could report in place of its true demand
+/// in `demands` to get a larger useful allocation from `alg`, while every
+/// other tenant reports the truth. Tries scaling each resource of the true
+/// demand on a grid, including claiming resources it does not need, then
+/// `num_samples` random misreports. Returns the truth if no lie helps.
+pub fn find_misreport(
+    alg: &dyn Algorithm,
+    resources: &[f64],
+    demands: &[Vec<f64>],
+    tenant: usize,
+    num_samples: usize,
+    rng: &mut StdRng,
+) -> Misreport {
+    let demand = &demands[tenant];
+    let useful = |report: &[f64]| {
+        let mut reports = demands.to_vec();
+        reports[tenant] = report.to_vec();
+        let coeffs = alg.allocate(resources, &reports);
+        useful_allocation(demand, report, coeffs[tenant])
+    };
+    // What a lie scales: the true demand, or the largest one for resources
+    // the tenant does not need.
+    let largest = demand.iter().cloned().fold(0.0, f64::max);
+    let base: Vec<f64> = demand
+        .iter()
+        .map(|d| if *d > 0.0 { *d } else { largest })
+        .collect();
+
+    let truthful = useful(demand);
+    let mut best = Misreport {
+        tenant,
+        report: demand.clone(),
+        truthful,
+        useful: truthful,
+    };
+    let mut consider = |report: Vec<f64>| {
+        let useful = useful(&report);
+        if useful > best.useful {
+            best.report = report;
+            best.useful = useful;
+        }
+    };
+
+    for j in 0..demand.len() {
+        for factor in GRID_FACTORS {
+            let mut report = demand.clone();
+            report[j] = base[j] * factor;
+            consider(report);
+        }
+    }
+    let (low, high) = (RANDOM_FACTORS.0.ln(), RANDOM_FACTORS.1.ln());
+    for _ in 0..num_samples {
+        let report = demand
+            .iter()
+            .zip(&base)
+            .map(|(d, b)| {
+                if rng.gen_bool(0.5) {
+                    *d
+                } else {
+                    b * rng.gen_range(low, high).exp()
+                }
+            })
+            .collect();
+        consider(report);
+    }
+    best
+}
+
+/// Runs find_misreport() for each of `tenants` in turn, drawing the random
+/// misreports from a generator seeded with `seed`.
+pub fn find_misreports(
+    alg: &dyn Algorithm,
+    resources: &[f64],
+    demands: &[Vec<f64>],
+    tenants: &[usize],
+    num_samples: usize,
+    seed: u64,
+) -> Result<Vec<Misreport>, String> {
+    let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
+    tenants
+        .iter()
+        .map(|tenant| {
+            if *tenant >= demands.len() {
+                return Err(format!("tenant {} out of {}", tenant, demands.len()));
+            }
+            Ok(find_misreport(
+                alg,
+                resources,
+                demands,
+                *tenant,
+                num_samples,
+                &mut rng,
+            ))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Splits every resource equally, which no lie can beat.
+    struct StaticSplit;
+
+    impl Algorithm for StaticSplit {
+        fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+            let n = demands.len() as f64;
+            demands
+                .iter()
+                .map(|demand| {
+                    demand
+                        .iter()
+                        .zip(resources)
+                        .filter(|(d, _)| **d > 0.0)
+                        .map(|(d, r)| r / n / d)
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .collect()
+        }
+    }
+
+    /// Gives every tenant a share of each resource in proportion to its
+    /// total reported demand, which rewards exaggerating.
+    struct ByTotal;
+
+    impl Algorithm for ByTotal {
+        fn allocate(&self, resources: &[f64], demands: &[Vec<f64>]) -> Vec<f64> {
+            let total: f64 = demands.iter().flatten().sum();
+            demands
+                .iter()
+                .map(|demand| {
+                    let share = demand.iter().sum::<f64>() / total;
+                    demand
+                        .iter()
+                        .zip(resources)
+                        .filter(|(d, _)| **d > 0.0)
+                        .map(|(d, r)| share * r / d)
+                        .fold(f64::INFINITY, f64::min)
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn useful() {
+        assert_eq!(useful_allocation(&[1.0, 2.0], &[1.0, 2.0], 3.0), 3.0);
+        // Over-reporting the second resource is wasted, under-reporting the
+        // first limits what the tenant can run.
+        assert_eq!(useful_allocation(&[1.0, 2.0], &[0.5, 4.0], 2.0), 1.0);
+        assert_eq!(useful_allocation(&[1.0, 0.0], &[1.0, 4.0], 2.0), 2.0);
+    }
+
+    #[test]
+    fn search() {
+        let resources = [9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+
+        let misreport = find_misreport(&StaticSplit, &resources, &demands, 0, 200, &mut rng);
+        assert_eq!(misreport.truthful, 2.25);
+        assert_eq!(misreport.report, demands[0]);
+        assert_eq!(misreport.gain(), 0.0);
+
+        let misreport = find_misreport(&ByTotal, &resources, &demands, 0, 200, &mut rng);
+        assert!(misreport.gain() > 0.0);
+        assert!(misreport.report[0] > demands[0][0]);
+    }
+
+    #[test]
+    fn every_tenant() {
+        let resources = [9.0, 18.0];
+        let demands = vec![vec![1.0, 4.0], vec![3.0, 1.0]];
+        let misreports =
+            find_misreports(&StaticSplit, &resources, &demands, &[1, 0], 10, 1).unwrap();
+        assert_eq!(misreports.len(), 2);
+        assert_eq!(misreports[0].tenant, 1);
+        assert!(misreports[1]
+            .to_string()
+            .starts_with("tenant_0: truthful 2.2500, best report [1.0, 4.0]"));
+        assert!(find_misreports(&StaticSplit, &resources, &demands, &[2], 10, 1).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use std::fmt;
+
+/// How a resource is consumed by the packets using it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResourceKind {
+    /// A divisible rate, e.g. cycles/s or PCIe bandwidth. A packet getting a
+    /// fraction of its demand is served at that fraction of its speed.
+    Rate,
+    /// Occupancy, e.g. SRAM or table entries. A packet holds all of its
+    /// demand from the moment it is granted until it completes, and makes no
+    /// progress without it.
+    Space,
+    /// Buffer memory in bytes, e.g. on-NIC packet memory. A packet holds its
+    /// size of it from the moment it arrives, waiting or in service, until
+    /// it completes, and is dropped on arrival if it does not fit.
+    Memory,
+}
+
+/// A named resource of the device.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Resource {
+    pub name: String,
+    pub kind: ResourceKind,
+}
+
+impl Resource {
+    pub fn new(name: &str, kind: ResourceKind) -> Resource {
+        Resource {
+            name: name.to_string(),
+            kind,
+        }
+    }
+
+    /// Parses `name`, `name:rate`, `name:space` or `name:memory`. Resources
+    /// are rates unless said otherwise.
+    pub fn from_spec(spec: &str) -> Result<Resource, String> {
+        let mut parts = spec.splitn(2, ':');
+        let name = parts.next().unwrap().trim();
+        if name.is_empty() {
+            return Err(format!("{}: resource needs a name", spec));
+        }
+        let kind = match parts.next().map(|kind| kind.trim()) {
+            None | Some("rate") => ResourceKind::Rate,
+            Some("space") => ResourceKind::Space,
+            Some("memory") => ResourceKind::Memory,
+            Some(kind) => return Err(format!("{}: unknown resource kind {}", spec, kind)),
+        };
+        Ok(Resource::new(name, kind))
+    }
+
+    /// `num_resources` rates named `r0`, `r1`, ...
+    pub fn defaults(num_resources: usize) -> Vec<Resource> {
+        (0..num_resources)
+            .map(|j| Resource::new(&format!("r{}", j), ResourceKind::Rate))
+            .collect()
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ResourceKind::Rate => write!(f, "{}:rate", self.name),
+            ResourceKind::Space => write!(f, "{}:space", self.name),
+            ResourceKind::Memory => write!(f, "{}:memory", self.name),
+        }
+    }
+}
+
+/// Formats `amounts` of each of `resources` as `[name=amount, ...]`.
+pub fn format_named(resources: &[Resource], amounts: &[f64]) -> String {
+    let named: Vec<String> = resources
+        .iter()
+        .zip(amounts)
+        .map(|(resource, amount)| format!("{}={}", resource.name, amount))
+        .collect();
+    format!("[{}]", named.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_spec() {
+        assert_eq!(
+            Resource::from_spec("cycles"),
+            Ok(Resource::new("cycles", ResourceKind::Rate))
+        );
+        assert_eq!(
+            Resource::from_spec("sram:space"),
+            Ok(Resource::new("sram", ResourceKind::Space))
+        );
+        assert!(Resource::from_spec(":rate").is_err());
+        assert_eq!(
+            Resource::from_spec("buffer:memory"),
+            Ok(Resource::new("buffer", ResourceKind::Memory))
+        );
+        assert!(Resource::from_spec("sram:disk").is_err());
+        assert_eq!(
+            Resource::from_spec("sram:space").unwrap().to_string(),
+            "sram:space"
+        );
+    }
+
+    #[test]
+    fn named() {
+        let resources = vec![
+            Resource::new("cycles", ResourceKind::Rate),
+            Resource::new("sram", ResourceKind::Space),
+        ];
+        assert_eq!(
+            format_named(&resources, &[1.5, 2.0]),
+            "[cycles=1.5, sram=2]"
+        );
+    }
+}
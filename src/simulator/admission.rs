@@ -0,0 +1,497 @@
+use super::Packet;
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Why an arriving packet was not admitted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DropReason {
+    /// The packet demands a negative or non-finite amount of some resource.
+    InvalidDemand,
+    /// The packet demands more of some resource than the device has.
+    OverCapacity,
+    /// The packet does not fit in what the packets in the device leave of a
+    /// memory resource.
+    BufferFull,
+    /// The shared queue is full.
+    QueueFull,
+    /// The queue of the packet's tenant is full.
+    TenantQueueFull,
+    /// Dropped early by RED.
+    Early,
+}
+
+impl fmt::Display for DropReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            DropReason::InvalidDemand => "invalid_demand",
+            DropReason::OverCapacity => "over_capacity",
+            DropReason::BufferFull => "buffer_full",
+            DropReason::QueueFull => "queue_full",
+            DropReason::TenantQueueFull => "tenant_queue_full",
+            DropReason::Early => "early",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What to do with a packet arriving at a busy shared queue.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DropPolicy {
+    /// Drop only when the shared queue is full.
+    TailDrop,
+    /// Random early detection: drop with a probability growing linearly from
+    /// 0 to `max_p` as the average queue length grows from `min_th` to
+    /// `max_th`, and always above `max_th`. The average is an exponentially
+    /// weighted moving average over arrivals with weight RED_WEIGHT.
+    Red {
+        min_th: f64,
+        max_th: f64,
+        max_p: f64,
+    },
+    /// Every tenant may hold at most an equal share of the shared queue.
+    Quota,
+}
+
+/// Weight of the latest queue length in RED's moving average.
+pub const RED_WEIGHT: f64 = 0.1;
+
+impl DropPolicy {
+    /// Parses `tail`, `red:min_th,max_th,max_p` or `quota`.
+    pub fn from_spec(spec: &str) -> Result<DropPolicy, String> {
+        let mut parts = spec.splitn(2, ':');
+        match (parts.next().unwrap().trim(), parts.next()) {
+            ("tail", None) => Ok(DropPolicy::TailDrop),
+            ("quota", None) => Ok(DropPolicy::Quota),
+            ("red", Some(args)) => {
+                let args = args
+                    .split(',')
+                    .map(|x| x.trim().parse::<f64>())
+                    .collect::<Result<Vec<f64>, _>>()
+                    .map_err(|e| format!("{}: {}", spec, e))?;
+                if args.len() != 3
+                    || args[0] < 0.0
+                    || args[0] >= args[1]
+                    || !(0.0..=1.0).contains(&args[2])
+                {
+                    return Err(format!(
+                        "{}: expected red:min_th,max_th,max_p with min_th < max_th and \
+                         max_p in [0, 1]",
+                        spec
+                    ));
+                }
+                Ok(DropPolicy::Red {
+                    min_th: args[0],
+                    max_th: args[1],
+                    max_p: args[2],
+                })
+            }
+            _ => Err(format!("{}: unknown drop policy", spec)),
+        }
+    }
+}
+
+/// Buffer limits of the device. Limits count packets that arrived and have
+/// not completed yet.
+#[derive(Clone, Debug)]
+pub struct QueueConfig {
+    /// Packets the device holds across all tenants, unlimited if None.
+    pub limit: Option<usize>,
+    /// Packets the device holds per tenant, unlimited if None.
+    pub tenant_limit: Option<usize>,
+    pub policy: DropPolicy,
+    /// Refuse packets that demand more of any resource than its capacity.
+    pub admission_control: bool,
+}
+
+impl Default for QueueConfig {
+    fn default() -> QueueConfig {
+        QueueConfig {
+            limit: None,
+            tenant_limit: None,
+            policy: DropPolicy::TailDrop,
+            admission_control: true,
+        }
+    }
+}
+
+/// Decides which arriving packets enter the device and counts the dropped
+/// ones.
+pub struct Admission {
+    config: QueueConfig,
+    num_tenants: usize,
+    /// Indices of the memory resources, which every packet in the device
+    /// holds its demand of.
+    memory: Vec<usize>,
+    /// RED's average queue length.
+    avg_len: f64,
+    drops: BTreeMap<(usize, DropReason), u64>,
+}
+
+impl Admission {
+    pub fn new(config: QueueConfig, num_tenants: usize) -> Admission {
+        Admission {
+            config,
+            num_tenants,
+            memory: Vec::new(),
+            avg_len: 0.0,
+            drops: BTreeMap::new(),
+        }
+    }
+
+    /// Also drops packets that do not fit in the resources `memory`, see
+    /// ResourceKind::Memory.
+    pub fn with_memory(mut self, memory: Vec<usize>) -> Admission {
+        self.memory = memory;
+        self
+    }
+
+    /// Decides whether a packet of `tenant` demanding `resource_req` enters
+    /// the device of nominal `capacity`, given the packets currently in it,
+    /// waiting or in service, and the memory `available` now, which a
+    /// capacity schedule may have shrunk. Dropped packets are counted.
+    pub fn admit<'a, I>(
+        &mut self,
+        tenant: usize,
+        resource_req: &[f64],
+        capacity: &[f64],
+        available: &[f64],
+        in_device: I,
+        rng: &mut StdRng,
+    ) -> Result<(), DropReason>
+    where
+        I: Iterator<Item = &'a Packet>,
+    {
+        let mut len = 0;
+        let mut tenant_len = 0;
+        let mut held = vec![0.0; self.memory.len()];
+        for pkt in in_device {
+            len += 1;
+            if pkt.tenant() == tenant {
+                tenant_len += 1;
+            }
+            for (held, j) in held.iter_mut().zip(&self.memory) {
+                *held += pkt.resource_req[*j];
+            }
+        }
+        let result = self.check(
+            resource_req,
+            capacity,
+            available,
+            &held,
+            len,
+            tenant_len,
+            rng,
+        );
+        if let Err(reason) = result {
+            *self.drops.entry((tenant, reason)).or_insert(0) += 1;
+        }
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn check(
+        &mut self,
+        resource_req: &[f64],
+        capacity: &[f64],
+        available: &[f64],
+        held: &[f64],
+        len: usize,
+        tenant_len: usize,
+        rng: &mut StdRng,
+    ) -> Result<(), DropReason> {
+        if resource_req
+            .iter()
+            .any(|req| !(req.is_finite() && *req >= 0.0))
+        {
+            return Err(DropReason::InvalidDemand);
+        }
+        if self.config.admission_control
+            && resource_req
+                .iter()
+                .zip(capacity)
+                .any(|(req, cap)| req > cap)
+        {
+            return Err(DropReason::OverCapacity);
+        }
+        // Memory is physical, so it bounds the device even without
+        // admission control.
+        if self
+            .memory
+            .iter()
+            .zip(held)
+            .any(|(j, held)| held + resource_req[*j] > available[*j])
+        {
+            return Err(DropReason::BufferFull);
+        }
+
+        if let Some(tenant_limit) = self.config.tenant_limit {
+            if tenant_len >= tenant_limit {
+                return Err(DropReason::TenantQueueFull);
+            }
+        }
+        if let Some(limit) = self.config.limit {
+            if len >= limit {
+                return Err(DropReason::QueueFull);
+            }
+        }
+
+        match self.config.policy {
+            DropPolicy::TailDrop => Ok(()),
+            DropPolicy::Red {
+                min_th,
+                max_th,
+                max_p,
+            } => {
+                self.avg_len = (1.0 - RED_WEIGHT) * self.avg_len + RED_WEIGHT * len as f64;
+                let p = if self.avg_len < min_th {
+                    0.0
+                } else if self.avg_len >= max_th {
+                    1.0
+                } else {
+                    max_p * (self.avg_len - min_th) / (max_th - min_th)
+                };
+                if p > 0.0 && rng.gen_bool(p) {
+                    Err(DropReason::Early)
+                } else {
+                    Ok(())
+                }
+            }
+            DropPolicy::Quota => {
+                let limit = self
+                    .config
+                    .limit
+                    .expect("the quota drop policy needs a queue limit");
+                let quota = limit.div_ceil(self.num_tenants.max(1));
+                if tenant_len >= quota {
+                    Err(DropReason::TenantQueueFull)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    /// Number of dropped packets per tenant and reason.
+    pub fn drops(&self) -> &BTreeMap<(usize, DropReason), u64> {
+        &self.drops
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn queued(tenants: &[usize]) -> Vec<Packet> {
+        tenants
+            .iter()
+            .enumerate()
+            .map(|(i, tenant)| Packet::new(i as u64, 0, 10.0, vec![1.0]).with_tenant(*tenant))
+            .collect()
+    }
+
+    #[test]
+    fn limits() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let config = QueueConfig {
+            limit: Some(3),
+            tenant_limit: Some(2),
+            ..Default::default()
+        };
+        let mut admission = Admission::new(config, 2);
+        let capacity = [10.0];
+        assert_eq!(
+            admission.admit(0, &[11.0], &capacity, &capacity, [].iter(), &mut rng),
+            Err(DropReason::OverCapacity)
+        );
+        assert_eq!(
+            admission.admit(0, &[-1.0], &capacity, &capacity, [].iter(), &mut rng),
+            Err(DropReason::InvalidDemand)
+        );
+        assert_eq!(
+            admission.admit(0, &[f64::NAN], &capacity, &capacity, [].iter(), &mut rng),
+            Err(DropReason::InvalidDemand)
+        );
+        assert_eq!(
+            admission.admit(
+                0,
+                &[1.0],
+                &capacity,
+                &capacity,
+                queued(&[0, 0]).iter(),
+                &mut rng
+            ),
+            Err(DropReason::TenantQueueFull)
+        );
+        assert_eq!(
+            admission.admit(
+                1,
+                &[1.0],
+                &capacity,
+                &capacity,
+                queued(&[0, 0]).iter(),
+                &mut rng
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            admission.admit(
+                1,
+                &[1.0],
+                &capacity,
+                &capacity,
+                queued(&[0, 0, 1]).iter(),
+                &mut rng
+            ),
+            Err(DropReason::QueueFull)
+        );
+        assert_eq!(admission.drops().values().sum::<u64>(), 5);
+        assert_eq!(admission.drops()[&(0, DropReason::InvalidDemand)], 2);
+        assert_eq!(admission.drops()[&(0, DropReason::OverCapacity)], 1);
+        assert_eq!(admission.drops()[&(1, DropReason::QueueFull)], 1);
+    }
+
+    #[test]
+    fn memory() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let config = QueueConfig {
+            admission_control: false,
+            ..Default::default()
+        };
+        let mut admission = Admission::new(config, 1).with_memory(vec![1]);
+        let capacity = [1.0, 1500.0];
+        let in_device = [Packet::new(0, 0, 1.0, vec![5.0, 1000.0])];
+        assert_eq!(
+            admission.admit(
+                0,
+                &[5.0, 500.0],
+                &capacity,
+                &capacity,
+                in_device.iter(),
+                &mut rng
+            ),
+            Ok(())
+        );
+        assert_eq!(
+            admission.admit(
+                0,
+                &[0.0, 501.0],
+                &capacity,
+                &capacity,
+                in_device.iter(),
+                &mut rng
+            ),
+            Err(DropReason::BufferFull)
+        );
+        // A buffer shrunk by a capacity schedule holds less.
+        let available = [1.0, 1200.0];
+        assert_eq!(
+            admission.admit(
+                0,
+                &[0.0, 500.0],
+                &capacity,
+                &available,
+                in_device.iter(),
+                &mut rng
+            ),
+            Err(DropReason::BufferFull)
+        );
+    }
+
+    #[test]
+    fn quota() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let config = QueueConfig {
+            limit: Some(4),
+            policy: DropPolicy::Quota,
+            ..Default::default()
+        };
+        let mut admission = Admission::new(config, 2);
+        let capacity = [10.0];
+        assert_eq!(
+            admission.admit(
+                0,
+                &[1.0],
+                &capacity,
+                &capacity,
+                queued(&[0, 0, 1]).iter(),
+                &mut rng
+            ),
+            Err(DropReason::TenantQueueFull)
+        );
+        assert_eq!(
+            admission.admit(
+                1,
+                &[1.0],
+                &capacity,
+                &capacity,
+                queued(&[0, 0, 1]).iter(),
+                &mut rng
+            ),
+            Ok(())
+        );
+
+        let config = QueueConfig {
+            limit: Some(1),
+            policy: DropPolicy::Quota,
+            ..Default::default()
+        };
+        let mut admission = Admission::new(config, 0);
+        assert_eq!(
+            admission.admit(0, &[1.0], &capacity, &capacity, [].iter(), &mut rng),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn red() {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let config = QueueConfig {
+            policy: DropPolicy::from_spec("red:2,6,0.5").unwrap(),
+            ..Default::default()
+        };
+        let mut admission = Admission::new(config, 1);
+        let capacity = [10.0];
+        // Short queues never drop.
+        for _ in 0..100 {
+            assert!(admission
+                .admit(
+                    0,
+                    &[1.0],
+                    &capacity,
+                    &capacity,
+                    queued(&[0]).iter(),
+                    &mut rng
+                )
+                .is_ok());
+        }
+        // Long queues always drop once the average catches up.
+        let long = queued(&[0; 10]);
+        for _ in 0..100 {
+            let _ = admission.admit(0, &[1.0], &capacity, &capacity, long.iter(), &mut rng);
+        }
+        assert_eq!(
+            admission.admit(0, &[1.0], &capacity, &capacity, long.iter(), &mut rng),
+            Err(DropReason::Early)
+        );
+    }
+
+    #[test]
+    fn policy_from_spec() {
+        assert_eq!(DropPolicy::from_spec("tail"), Ok(DropPolicy::TailDrop));
+        assert_eq!(DropPolicy::from_spec("quota"), Ok(DropPolicy::Quota));
+        assert_eq!(
+            DropPolicy::from_spec("red:5,15,0.1"),
+            Ok(DropPolicy::Red {
+                min_th: 5.0,
+                max_th: 15.0,
+                max_p: 0.1
+            })
+        );
+        assert!(DropPolicy::from_spec("red:15,5,0.1").is_err());
+        assert!(DropPolicy::from_spec("red").is_err());
+        assert!(DropPolicy::from_spec("fifo").is_err());
+    }
+}
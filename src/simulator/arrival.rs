@@ -0,0 +1,327 @@
+use rand::distributions::{Bernoulli, Distribution};
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand_distr::Poisson;
+use std::f64::consts::PI;
+
+/// Decides how many packets of a tenant arrive in each tick.
+pub trait ArrivalProcess {
+    /// Returns the number of packets arriving in tick `t`. Called once per
+    /// tick, with increasing `t`.
+    fn arrivals(&mut self, t: u64, rng: &mut StdRng) -> u64;
+}
+
+/// At most one packet per tick, arriving with probability `p`.
+pub struct BernoulliArrivals {
+    dist: Bernoulli,
+}
+
+impl BernoulliArrivals {
+    pub fn new(p: f64) -> BernoulliArrivals {
+        BernoulliArrivals {
+            dist: Bernoulli::new(p).unwrap(),
+        }
+    }
+}
+
+impl ArrivalProcess for BernoulliArrivals {
+    fn arrivals(&mut self, _t: u64, rng: &mut StdRng) -> u64 {
+        self.dist.sample(rng) as u64
+    }
+}
+
+/// Samples a Poisson distributed number of arrivals with mean `rate`.
+fn sample_poisson(rate: f64, rng: &mut StdRng) -> u64 {
+    if rate <= 0.0 {
+        return 0;
+    }
+    Poisson::new(rate).unwrap().sample(rng)
+}
+
+/// Poisson arrivals with `rate` packets per tick on average, so several
+/// packets may arrive in the same tick.
+pub struct PoissonArrivals {
+    rate: f64,
+}
+
+impl PoissonArrivals {
+    pub fn new(rate: f64) -> PoissonArrivals {
+        assert!(rate >= 0.0);
+        PoissonArrivals { rate }
+    }
+}
+
+impl ArrivalProcess for PoissonArrivals {
+    fn arrivals(&mut self, _t: u64, rng: &mut StdRng) -> u64 {
+        sample_poisson(self.rate, rng)
+    }
+}
+
+/// Markov-modulated Poisson arrivals alternating between an on (burst) and
+/// an off state. The state is kept for a geometrically distributed number of
+/// ticks: it switches from on to off with probability `p_on_off` and from off
+/// to on with probability `p_off_on` at the start of every tick.
+pub struct OnOffArrivals {
+    on_rate: f64,
+    off_rate: f64,
+    p_on_off: f64,
+    p_off_on: f64,
+    on: bool,
+}
+
+impl OnOffArrivals {
+    /// Starts in the on state.
+    pub fn new(on_rate: f64, off_rate: f64, p_on_off: f64, p_off_on: f64) -> OnOffArrivals {
+        assert!(on_rate >= 0.0 && off_rate >= 0.0);
+        assert!((0.0..=1.0).contains(&p_on_off) && (0.0..=1.0).contains(&p_off_on));
+        OnOffArrivals {
+            on_rate,
+            off_rate,
+            p_on_off,
+            p_off_on,
+            on: true,
+        }
+    }
+}
+
+impl ArrivalProcess for OnOffArrivals {
+    fn arrivals(&mut self, t: u64, rng: &mut StdRng) -> u64 {
+        if t > 0 {
+            let p_switch = if self.on {
+                self.p_on_off
+            } else {
+                self.p_off_on
+            };
+            if rng.gen_bool(p_switch) {
+                self.on = !self.on;
+            }
+        }
+        let rate = if self.on { self.on_rate } else { self.off_rate };
+        sample_poisson(rate, rng)
+    }
+}
+
+/// Exactly one packet every `period` ticks, starting at tick `offset`.
+pub struct PeriodicArrivals {
+    period: u64,
+    offset: u64,
+}
+
+impl PeriodicArrivals {
+    pub fn new(period: u64, offset: u64) -> PeriodicArrivals {
+        assert!(period > 0);
+        PeriodicArrivals { period, offset }
+    }
+}
+
+impl ArrivalProcess for PeriodicArrivals {
+    fn arrivals(&mut self, t: u64, _rng: &mut StdRng) -> u64 {
+        (t >= self.offset && (t - self.offset).is_multiple_of(self.period)) as u64
+    }
+}
+
+/// Poisson arrivals whose rate follows a sine curve over `period` ticks,
+/// i.e. `mean * (1 + amplitude * sin(2 pi t / period))`, peaking a quarter
+/// period in.
+pub struct DiurnalArrivals {
+    mean: f64,
+    amplitude: f64,
+    period: u64,
+}
+
+impl DiurnalArrivals {
+    pub fn new(mean: f64, amplitude: f64, period: u64) -> DiurnalArrivals {
+        assert!(mean >= 0.0 && period > 0);
+        DiurnalArrivals {
+            mean,
+            amplitude,
+            period,
+        }
+    }
+
+    pub fn rate(&self, t: u64) -> f64 {
+        let phase = 2.0 * PI * (t % self.period) as f64 / self.period as f64;
+        f64::max(0.0, self.mean * (1.0 + self.amplitude * phase.sin()))
+    }
+}
+
+impl ArrivalProcess for DiurnalArrivals {
+    fn arrivals(&mut self, t: u64, rng: &mut StdRng) -> u64 {
+        sample_poisson(self.rate(t), rng)
+    }
+}
+
+/// Creates an arrival process from a spec of the form `kind:arg,arg,...`:
+///
+/// * `bernoulli:p`
+/// * `poisson:rate`
+/// * `onoff:on_rate,off_rate,p_on_off,p_off_on`
+/// * `periodic:period[,offset]`
+/// * `diurnal:mean,amplitude,period`
+pub fn arrival_process_from_spec(spec: &str) -> Result<Box<dyn ArrivalProcess>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap().trim();
+    let args: Vec<f64> = match parts.next() {
+        Some(args) => args
+            .split(',')
+            .map(|x| x.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("{}: {}", spec, e))?,
+        None => Vec::new(),
+    };
+    let expect_args = |counts: &[usize]| {
+        if counts.contains(&args.len()) {
+            Ok(())
+        } else {
+            Err(format!(
+                "{}: {} takes {:?} arguments, got {}",
+                spec,
+                kind,
+                counts,
+                args.len()
+            ))
+        }
+    };
+
+    let rate = |i: usize| {
+        if args[i].is_finite() && args[i] >= 0.0 {
+            Ok(args[i])
+        } else {
+            Err(format!("{}: rate must be finite and not negative", spec))
+        }
+    };
+    let probability = |i: usize| {
+        if (0.0..=1.0).contains(&args[i]) {
+            Ok(args[i])
+        } else {
+            Err(format!("{}: probability must be in [0, 1]", spec))
+        }
+    };
+    let ticks = |i: usize, min: f64| {
+        if args[i] >= min && args[i].fract() == 0.0 && args[i] <= u64::MAX as f64 {
+            Ok(args[i] as u64)
+        } else {
+            Err(format!(
+                "{}: ticks must be a whole number of at least {}",
+                spec, min
+            ))
+        }
+    };
+
+    match kind {
+        "bernoulli" => {
+            expect_args(&[1])?;
+            Ok(Box::new(BernoulliArrivals::new(probability(0)?)))
+        }
+        "poisson" => {
+            expect_args(&[1])?;
+            Ok(Box::new(PoissonArrivals::new(rate(0)?)))
+        }
+        "onoff" => {
+            expect_args(&[4])?;
+            Ok(Box::new(OnOffArrivals::new(
+                rate(0)?,
+                rate(1)?,
+                probability(2)?,
+                probability(3)?,
+            )))
+        }
+        "periodic" => {
+            expect_args(&[1, 2])?;
+            let offset = if args.len() > 1 { ticks(1, 0.0)? } else { 0 };
+            Ok(Box::new(PeriodicArrivals::new(ticks(0, 1.0)?, offset)))
+        }
+        "diurnal" => {
+            expect_args(&[3])?;
+            let amplitude = args[1];
+            if !amplitude.is_finite() {
+                return Err(format!("{}: amplitude must be finite", spec));
+            }
+            Ok(Box::new(DiurnalArrivals::new(
+                rate(0)?,
+                amplitude,
+                ticks(2, 1.0)?,
+            )))
+        }
+        _ => Err(format!("{}: unknown arrival process {}", spec, kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn total_arrivals(process: &mut dyn ArrivalProcess, ticks: u64) -> u64 {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        (0..ticks).map(|t| process.arrivals(t, &mut rng)).sum()
+    }
+
+    #[test]
+    fn periodic() {
+        let mut process = PeriodicArrivals::new(10, 3);
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let ticks: Vec<u64> = (0..30)
+            .filter(|t| process.arrivals(*t, &mut rng) == 1)
+            .collect();
+        assert_eq!(ticks, [3, 13, 23]);
+    }
+
+    #[test]
+    fn mean_rates() {
+        let ticks = 10000;
+        let bernoulli = total_arrivals(&mut BernoulliArrivals::new(0.3), ticks);
+        assert!((2800..3200).contains(&bernoulli), "{}", bernoulli);
+        let poisson = total_arrivals(&mut PoissonArrivals::new(2.5), ticks);
+        assert!((24000..26000).contains(&poisson), "{}", poisson);
+        let diurnal = total_arrivals(&mut DiurnalArrivals::new(1.0, 0.5, 100), ticks);
+        assert!((9500..10500).contains(&diurnal), "{}", diurnal);
+    }
+
+    #[test]
+    fn on_off() {
+        // Never leaves the on state.
+        let on = total_arrivals(&mut OnOffArrivals::new(1.0, 0.0, 0.0, 1.0), 1000);
+        assert!(on > 900, "{}", on);
+        // Leaves the on state after the first tick and never comes back.
+        let off = total_arrivals(&mut OnOffArrivals::new(1.0, 0.0, 1.0, 0.0), 1000);
+        assert!(off < 10, "{}", off);
+    }
+
+    #[test]
+    fn from_spec() {
+        assert!(arrival_process_from_spec("bernoulli:0.3").is_ok());
+        assert!(arrival_process_from_spec("poisson:2").is_ok());
+        assert!(arrival_process_from_spec("onoff:2,0,0.1,0.05").is_ok());
+        assert!(arrival_process_from_spec("periodic:10").is_ok());
+        assert!(arrival_process_from_spec("periodic:10,5").is_ok());
+        assert!(arrival_process_from_spec("diurnal:1,0.5,1000").is_ok());
+        assert!(arrival_process_from_spec("bernoulli:1.5").is_err());
+        assert!(arrival_process_from_spec("poisson").is_err());
+        assert!(arrival_process_from_spec("poisson:x").is_err());
+        assert!(arrival_process_from_spec("uniform:1").is_err());
+    }
+
+    #[test]
+    fn from_spec_out_of_range() {
+        for spec in &[
+            "poisson:-1",
+            "poisson:NaN",
+            "poisson:inf",
+            "onoff:-1,0,0.1,0.1",
+            "onoff:1,0,1.5,0",
+            "onoff:1,0,0.5,NaN",
+            "periodic:0",
+            "periodic:2.5",
+            "periodic:-3",
+            "periodic:10,1.5",
+            "periodic:10,-1",
+            "diurnal:1,0.5,0",
+            "diurnal:-1,0.5,10",
+            "diurnal:1,NaN,10",
+            "diurnal:1,0.5,2.5",
+        ] {
+            assert!(arrival_process_from_spec(spec).is_err(), "{}", spec);
+        }
+    }
+}
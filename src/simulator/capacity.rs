@@ -0,0 +1,284 @@
+use rand::rngs::StdRng;
+use rand_distr::{Binomial, Distribution};
+
+/// Changes the capacity of the device over time.
+pub trait CapacitySchedule {
+    /// Adjusts `capacity` for tick `t`. Called once per tick, with increasing
+    /// `t`, on the capacity left by the schedules before it, starting from
+    /// the nominal capacity.
+    fn apply(&mut self, t: u64, capacity: &mut [f64], rng: &mut StdRng);
+}
+
+/// Sets `resource` to `capacity` from tick `t` on.
+pub struct StepChange {
+    t: u64,
+    resource: usize,
+    capacity: f64,
+}
+
+impl StepChange {
+    pub fn new(t: u64, resource: usize, capacity: f64) -> StepChange {
+        assert!(capacity >= 0.0);
+        StepChange {
+            t,
+            resource,
+            capacity,
+        }
+    }
+}
+
+impl CapacitySchedule for StepChange {
+    fn apply(&mut self, t: u64, capacity: &mut [f64], _rng: &mut StdRng) {
+        if t >= self.t {
+            capacity[self.resource] = self.capacity;
+        }
+    }
+}
+
+/// Throttles `resource` to `capacity` for the first `duration` ticks of
+/// every `period` ticks.
+pub struct Maintenance {
+    resource: usize,
+    period: u64,
+    duration: u64,
+    capacity: f64,
+}
+
+impl Maintenance {
+    pub fn new(resource: usize, period: u64, duration: u64, capacity: f64) -> Maintenance {
+        assert!(period > 0 && capacity >= 0.0);
+        Maintenance {
+            resource,
+            period,
+            duration,
+            capacity,
+        }
+    }
+}
+
+impl CapacitySchedule for Maintenance {
+    fn apply(&mut self, t: u64, capacity: &mut [f64], _rng: &mut StdRng) {
+        if t % self.period < self.duration {
+            capacity[self.resource] = self.capacity;
+        }
+    }
+}
+
+/// `resource` is made of `units` identical units, e.g. the engines of a NIC.
+/// At the start of every tick each working unit fails with probability
+/// `p_fail` and each failed unit recovers with probability `p_recover`. The
+/// resource keeps the share of its capacity that is still working.
+pub struct UnitFailures {
+    resource: usize,
+    units: u64,
+    p_fail: f64,
+    p_recover: f64,
+    failed: u64,
+}
+
+impl UnitFailures {
+    /// Starts with every unit working.
+    pub fn new(resource: usize, units: u64, p_fail: f64, p_recover: f64) -> UnitFailures {
+        assert!(units > 0);
+        assert!((0.0..=1.0).contains(&p_fail) && (0.0..=1.0).contains(&p_recover));
+        UnitFailures {
+            resource,
+            units,
+            p_fail,
+            p_recover,
+            failed: 0,
+        }
+    }
+}
+
+impl CapacitySchedule for UnitFailures {
+    fn apply(&mut self, _t: u64, capacity: &mut [f64], rng: &mut StdRng) {
+        let failing = Binomial::new(self.units - self.failed, self.p_fail)
+            .unwrap()
+            .sample(rng);
+        let recovering = Binomial::new(self.failed, self.p_recover)
+            .unwrap()
+            .sample(rng);
+        self.failed = self.failed + failing - recovering;
+        let working = (self.units - self.failed) as f64 / self.units as f64;
+        capacity[self.resource] *= working;
+    }
+}
+
+/// Creates a capacity schedule from a spec of the form `kind:arg,...`, where
+/// resources are indices into the capacity vector:
+///
+/// * `step:t,resource,capacity`
+/// * `maintenance:resource,period,duration,capacity`
+/// * `failure:resource,units,p_fail,p_recover`
+pub fn capacity_schedule_from_spec(
+    spec: &str,
+    num_resources: usize,
+) -> Result<Box<dyn CapacitySchedule>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap().trim();
+    let args: Vec<f64> = match parts.next() {
+        Some(args) => args
+            .split(',')
+            .map(|x| x.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("{}: {}", spec, e))?,
+        None => Vec::new(),
+    };
+    let expect_args = |count: usize| {
+        if args.len() == count {
+            Ok(())
+        } else {
+            Err(format!(
+                "{}: {} takes {} arguments, got {}",
+                spec,
+                kind,
+                count,
+                args.len()
+            ))
+        }
+    };
+    let whole = |i: usize, min: f64| {
+        if args[i] >= min && args[i].fract() == 0.0 && args[i] <= u64::MAX as f64 {
+            Ok(args[i] as u64)
+        } else {
+            Err(format!(
+                "{}: {} must be a whole number of at least {}",
+                spec, args[i], min
+            ))
+        }
+    };
+    let resource = |i: usize| {
+        let resource = whole(i, 0.0)? as usize;
+        if resource < num_resources {
+            Ok(resource)
+        } else {
+            Err(format!(
+                "{}: resource {} out of {}",
+                spec, resource, num_resources
+            ))
+        }
+    };
+    let non_negative = |i: usize| {
+        if args[i].is_finite() && args[i] >= 0.0 {
+            Ok(args[i])
+        } else {
+            Err(format!("{}: {} must not be negative", spec, args[i]))
+        }
+    };
+    let probability = |i: usize| {
+        if (0.0..=1.0).contains(&args[i]) {
+            Ok(args[i])
+        } else {
+            Err(format!("{}: probability must be in [0, 1]", spec))
+        }
+    };
+
+    match kind {
+        "step" => {
+            expect_args(3)?;
+            Ok(Box::new(StepChange::new(
+                whole(0, 0.0)?,
+                resource(1)?,
+                non_negative(2)?,
+            )))
+        }
+        "maintenance" => {
+            expect_args(4)?;
+            Ok(Box::new(Maintenance::new(
+                resource(0)?,
+                whole(1, 1.0)?,
+                whole(2, 0.0)?,
+                non_negative(3)?,
+            )))
+        }
+        "failure" => {
+            expect_args(4)?;
+            Ok(Box::new(UnitFailures::new(
+                resource(0)?,
+                whole(1, 1.0)?,
+                probability(2)?,
+                probability(3)?,
+            )))
+        }
+        _ => Err(format!("{}: unknown capacity schedule {}", spec, kind)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn capacities(specs: &[&str], ticks: u64) -> Vec<Vec<f64>> {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let mut schedules: Vec<Box<dyn CapacitySchedule>> = specs
+            .iter()
+            .map(|spec| capacity_schedule_from_spec(spec, 2).unwrap())
+            .collect();
+        (0..ticks)
+            .map(|t| {
+                let mut capacity = vec![10.0, 20.0];
+                for schedule in &mut schedules {
+                    schedule.apply(t, &mut capacity, &mut rng);
+                }
+                capacity
+            })
+            .collect()
+    }
+
+    #[test]
+    fn step_and_maintenance() {
+        assert_eq!(
+            capacities(&["step:2,1,30", "maintenance:0,3,1,0"], 5),
+            [
+                [0.0, 20.0],
+                [10.0, 20.0],
+                [10.0, 30.0],
+                [0.0, 30.0],
+                [10.0, 30.0],
+            ]
+        );
+    }
+
+    #[test]
+    fn failure() {
+        let capacities = capacities(&["failure:0,4,0.1,0.2"], 1000);
+        assert!(capacities.iter().all(|capacity| capacity[1] == 20.0));
+        let mut levels: Vec<f64> = capacities.iter().map(|capacity| capacity[0]).collect();
+        assert!(levels
+            .iter()
+            .all(|level| [0.0, 2.5, 5.0, 7.5, 10.0].contains(level)));
+        levels.dedup();
+        assert!(levels.len() > 10);
+    }
+
+    #[test]
+    fn many_units() {
+        // Failures are sampled per tick, not per unit, so many units are
+        // cheap. About p_fail / (p_fail + p_recover) of them are down.
+        let capacities = capacities(&["failure:0,1000000000,0.1,0.2"], 100);
+        let working = capacities.last().unwrap()[0];
+        assert!((6.0..7.5).contains(&working), "{}", working);
+    }
+
+    #[test]
+    fn from_spec_errors() {
+        for spec in &[
+            "step:1,2,10",
+            "step:1,0",
+            "maintenance:0,0,1,5",
+            "failure:0,0,0.1,0.1",
+            "failure:0,4,1.5,0.1",
+            "failure:0,2.5,0.1,0.1",
+            "step:1,-1,10",
+            "step:1,0.7,10",
+            "step:1.5,0,10",
+            "maintenance:-1,3,1,0",
+            "maintenance:0,3,1,NaN",
+            "flood:1",
+        ] {
+            assert!(capacity_schedule_from_spec(spec, 2).is_err(), "{}", spec);
+        }
+    }
+}
@@ -0,0 +1,480 @@
+use rand::distributions::Distribution;
+use rand::rngs::StdRng;
+use rand::Rng;
+use rand_distr::{Exp, LogNormal, Pareto};
+use serde::Deserialize;
+
+/// A distribution of non-negative values, such as service times or resource
+/// demands.
+pub trait ValueDistribution {
+    fn sample(&self, rng: &mut StdRng) -> f64;
+}
+
+/// Integers drawn uniformly from `[low, high)`.
+pub struct RandInt {
+    low: i32,
+    high: i32,
+}
+
+impl RandInt {
+    pub fn new(low: i32, high: i32) -> RandInt {
+        assert!(low < high);
+        RandInt { low, high }
+    }
+}
+
+impl ValueDistribution for RandInt {
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        rng.gen_range(self.low, self.high) as f64
+    }
+}
+
+/// Reals drawn uniformly from `[low, high)`.
+pub struct Uniform {
+    low: f64,
+    high: f64,
+}
+
+impl Uniform {
+    pub fn new(low: f64, high: f64) -> Uniform {
+        assert!(low < high);
+        Uniform { low, high }
+    }
+}
+
+impl ValueDistribution for Uniform {
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        rng.gen_range(self.low, self.high)
+    }
+}
+
+/// Always the same value.
+pub struct Constant {
+    value: f64,
+}
+
+impl Constant {
+    pub fn new(value: f64) -> Constant {
+        Constant { value }
+    }
+}
+
+impl ValueDistribution for Constant {
+    fn sample(&self, _rng: &mut StdRng) -> f64 {
+        self.value
+    }
+}
+
+/// Exponential distribution with the given mean.
+pub struct Exponential {
+    dist: Exp<f64>,
+}
+
+impl Exponential {
+    pub fn new(mean: f64) -> Exponential {
+        assert!(mean > 0.0);
+        Exponential {
+            dist: Exp::new(1.0 / mean).unwrap(),
+        }
+    }
+}
+
+impl ValueDistribution for Exponential {
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        self.dist.sample(rng)
+    }
+}
+
+/// Log-normal distribution, i.e. `exp(N(mu, sigma))`.
+pub struct LogNormalValue {
+    dist: LogNormal<f64>,
+}
+
+impl LogNormalValue {
+    pub fn new(mu: f64, sigma: f64) -> LogNormalValue {
+        LogNormalValue {
+            dist: LogNormal::new(mu, sigma).unwrap(),
+        }
+    }
+}
+
+impl ValueDistribution for LogNormalValue {
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        self.dist.sample(rng)
+    }
+}
+
+/// Heavy-tailed Pareto distribution with minimum `scale`. The mean is
+/// infinite for `shape <= 1`.
+pub struct ParetoValue {
+    dist: Pareto<f64>,
+}
+
+impl ParetoValue {
+    pub fn new(scale: f64, shape: f64) -> ParetoValue {
+        ParetoValue {
+            dist: Pareto::new(scale, shape).unwrap(),
+        }
+    }
+}
+
+impl ValueDistribution for ParetoValue {
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        self.dist.sample(rng)
+    }
+}
+
+/// Empirical histogram: each value is drawn with probability proportional to
+/// its weight.
+pub struct Empirical {
+    values: Vec<f64>,
+    /// Cumulative weights, normalized so that the last one is 1.
+    cdf: Vec<f64>,
+}
+
+impl Empirical {
+    pub fn new(values: Vec<f64>, weights: &[f64]) -> Empirical {
+        Empirical {
+            values,
+            cdf: cumulative(weights),
+        }
+    }
+}
+
+impl ValueDistribution for Empirical {
+    fn sample(&self, rng: &mut StdRng) -> f64 {
+        self.values[pick(&self.cdf, rng)]
+    }
+}
+
+/// Normalized cumulative sums of `weights`.
+fn cumulative(weights: &[f64]) -> Vec<f64> {
+    assert!(!weights.is_empty());
+    assert!(weights.iter().all(|w| *w >= 0.0));
+    let total: f64 = weights.iter().sum();
+    assert!(total > 0.0);
+    let mut sum = 0.0;
+    weights
+        .iter()
+        .map(|w| {
+            sum += w;
+            sum / total
+        })
+        .collect()
+}
+
+/// Picks an index with the probabilities given by the cumulative weights.
+fn pick(cdf: &[f64], rng: &mut StdRng) -> usize {
+    let u: f64 = rng.gen();
+    cdf.iter().position(|c| u < *c).unwrap_or(cdf.len() - 1)
+}
+
+/// Creates a distribution from a spec of the form `kind:arg,arg,...`:
+///
+/// * `randint:low,high`
+/// * `uniform:low,high`
+/// * `const:value`
+/// * `exp:mean`
+/// * `lognormal:mu,sigma`
+/// * `pareto:scale,shape`
+/// * `empirical:value=weight,value=weight,...`
+pub fn distribution_from_spec(spec: &str) -> Result<Box<dyn ValueDistribution>, String> {
+    let mut parts = spec.splitn(2, ':');
+    let kind = parts.next().unwrap().trim();
+    let args = parts.next().unwrap_or("");
+
+    if kind == "empirical" {
+        let mut values = Vec::new();
+        let mut weights = Vec::new();
+        for bin in args.split(',') {
+            let mut bin = bin.splitn(2, '=');
+            let value = bin.next().unwrap().trim().parse::<f64>();
+            let weight = bin.next().map(|w| w.trim().parse::<f64>());
+            match (value, weight) {
+                (Ok(value), Some(Ok(weight)))
+                    if value.is_finite() && value >= 0.0 && weight.is_finite() && weight >= 0.0 =>
+                {
+                    values.push(value);
+                    weights.push(weight);
+                }
+                _ => {
+                    return Err(format!(
+                        "{}: expected value=weight pairs, neither negative",
+                        spec
+                    ))
+                }
+            }
+        }
+        if weights.iter().sum::<f64>() <= 0.0 {
+            return Err(format!("{}: weights must not all be 0", spec));
+        }
+        return Ok(Box::new(Empirical::new(values, &weights)));
+    }
+
+    let args: Vec<f64> = if args.is_empty() {
+        Vec::new()
+    } else {
+        args.split(',')
+            .map(|x| x.trim().parse::<f64>())
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("{}: {}", spec, e))?
+    };
+    if args.iter().any(|x| !x.is_finite()) {
+        return Err(format!("{}: arguments must be finite", spec));
+    }
+    let expect_args = |count: usize| {
+        if args.len() == count {
+            Ok(())
+        } else {
+            Err(format!(
+                "{}: {} takes {} arguments, got {}",
+                spec,
+                kind,
+                count,
+                args.len()
+            ))
+        }
+    };
+    let check = |ok: bool, msg: &str| {
+        if ok {
+            Ok(())
+        } else {
+            Err(format!("{}: {}", spec, msg))
+        }
+    };
+
+    match kind {
+        "randint" => {
+            expect_args(2)?;
+            check(
+                args.iter()
+                    .all(|x| x.fract() == 0.0 && x.abs() <= i32::MAX as f64),
+                "low and high must be integers",
+            )?;
+            let (low, high) = (args[0] as i32, args[1] as i32);
+            check(low >= 0, "low must not be negative")?;
+            check(low < high, "low must be less than high")?;
+            Ok(Box::new(RandInt::new(low, high)))
+        }
+        "uniform" => {
+            expect_args(2)?;
+            check(args[0] >= 0.0, "low must not be negative")?;
+            check(args[0] < args[1], "low must be less than high")?;
+            Ok(Box::new(Uniform::new(args[0], args[1])))
+        }
+        "const" => {
+            expect_args(1)?;
+            check(args[0] >= 0.0, "value must not be negative")?;
+            Ok(Box::new(Constant::new(args[0])))
+        }
+        "exp" => {
+            expect_args(1)?;
+            check(args[0] > 0.0, "mean must be positive")?;
+            Ok(Box::new(Exponential::new(args[0])))
+        }
+        "lognormal" => {
+            expect_args(2)?;
+            check(args[1] >= 0.0, "sigma must not be negative")?;
+            Ok(Box::new(LogNormalValue::new(args[0], args[1])))
+        }
+        "pareto" => {
+            expect_args(2)?;
+            check(
+                args[0] > 0.0 && args[1] > 0.0,
+                "scale and shape must be positive",
+            )?;
+            Ok(Box::new(ParetoValue::new(args[0], args[1])))
+        }
+        _ => Err(format!("{}: unknown distribution {}", spec, kind)),
+    }
+}
+
+/// Decides the resource demand vector of each packet.
+pub trait DemandProfile {
+    fn sample(&self, num_resources: usize, rng: &mut StdRng) -> Vec<f64>;
+}
+
+/// Every resource is drawn independently from the same distribution.
+pub struct IndependentDemand {
+    dist: Box<dyn ValueDistribution>,
+}
+
+impl IndependentDemand {
+    pub fn new(dist: Box<dyn ValueDistribution>) -> IndependentDemand {
+        IndependentDemand { dist }
+    }
+}
+
+impl DemandProfile for IndependentDemand {
+    fn sample(&self, num_resources: usize, rng: &mut StdRng) -> Vec<f64> {
+        (0..num_resources).map(|_| self.dist.sample(rng)).collect()
+    }
+}
+
+/// A class of packets with a typical demand vector, e.g. crypto-heavy
+/// packets that mostly need accelerator cycles. Unknown fields, such as a
+/// `name` for the class, are ignored when reading a scenario.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DemandClass {
+    /// Relative frequency of the class.
+    pub weight: f64,
+    /// Demand vector of a typical packet of the class.
+    pub demand: Vec<f64>,
+    /// Factor that scales the whole demand vector of a packet, so that the
+    /// demands of a packet are correlated across resources.
+    #[serde(default = "default_scale")]
+    pub scale: String,
+}
+
+fn default_scale() -> String {
+    "const:1".to_string()
+}
+
+/// Each packet belongs to one of several classes, picked by weight, and
+/// demands the demand vector of its class times a random scale.
+pub struct ClassDemand {
+    demands: Vec<Vec<f64>>,
+    scales: Vec<Box<dyn ValueDistribution>>,
+    cdf: Vec<f64>,
+}
+
+impl ClassDemand {
+    pub fn new(classes: &[DemandClass]) -> Result<ClassDemand, String> {
+        if classes.is_empty() {
+            return Err("need at least one demand class".to_string());
+        }
+        let weights: Vec<f64> = classes.iter().map(|class| class.weight).collect();
+        if weights.iter().any(|w| *w < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+            return Err("class weights must be non-negative and not all 0".to_string());
+        }
+        for class in classes {
+            if class.demand.is_empty() || class.demand.iter().any(|d| !(d.is_finite() && *d >= 0.0))
+            {
+                return Err(format!(
+                    "class demand {:?} must be finite, non-negative and not empty",
+                    class.demand
+                ));
+            }
+        }
+        Ok(ClassDemand {
+            demands: classes.iter().map(|class| class.demand.clone()).collect(),
+            scales: classes
+                .iter()
+                .map(|class| distribution_from_spec(&class.scale))
+                .collect::<Result<Vec<_>, _>>()?,
+            cdf: cumulative(&weights),
+        })
+    }
+}
+
+impl DemandProfile for ClassDemand {
+    fn sample(&self, num_resources: usize, rng: &mut StdRng) -> Vec<f64> {
+        let class = pick(&self.cdf, rng);
+        assert!(
+            self.demands[class].len() == num_resources,
+            "demand class has {} resources, capacity has {}",
+            self.demands[class].len(),
+            num_resources
+        );
+        let scale = self.scales[class].sample(rng);
+        self.demands[class].iter().map(|d| d * scale).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::mean;
+    use rand::SeedableRng;
+
+    fn samples(dist: &dyn ValueDistribution, n: usize) -> Vec<f64> {
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        (0..n).map(|_| dist.sample(&mut rng)).collect()
+    }
+
+    #[test]
+    fn means() {
+        let exp = samples(distribution_from_spec("exp:15").unwrap().as_ref(), 10000);
+        assert!((14.0..16.0).contains(&mean(&exp)), "{}", mean(&exp));
+        let randint = samples(
+            distribution_from_spec("randint:10,20").unwrap().as_ref(),
+            1000,
+        );
+        assert!(randint
+            .iter()
+            .all(|x| (10.0..20.0).contains(x) && x.fract() == 0.0));
+        // Mean of a log-normal is exp(mu + sigma^2 / 2).
+        let lognormal = samples(
+            distribution_from_spec("lognormal:1,0.5").unwrap().as_ref(),
+            10000,
+        );
+        assert!(
+            (2.95..3.2).contains(&mean(&lognormal)),
+            "{}",
+            mean(&lognormal)
+        );
+        let pareto = samples(distribution_from_spec("pareto:2,3").unwrap().as_ref(), 1000);
+        assert!(pareto.iter().all(|x| *x >= 2.0));
+    }
+
+    #[test]
+    fn empirical() {
+        let dist = distribution_from_spec("empirical:1=0,5=1,9=3").unwrap();
+        let values = samples(dist.as_ref(), 10000);
+        assert!(values.iter().all(|x| *x == 5.0 || *x == 9.0));
+        let nines = values.iter().filter(|x| **x == 9.0).count();
+        assert!((7300..7700).contains(&nines), "{}", nines);
+    }
+
+    #[test]
+    fn from_spec_errors() {
+        assert!(distribution_from_spec("exp").is_err());
+        assert!(distribution_from_spec("exp:-1").is_err());
+        assert!(distribution_from_spec("randint:5,5").is_err());
+        assert!(distribution_from_spec("empirical:1").is_err());
+        assert!(distribution_from_spec("empirical:1=0").is_err());
+        assert!(distribution_from_spec("gamma:1,2").is_err());
+        assert!(distribution_from_spec("randint:1.2,1.7").is_err());
+        assert!(distribution_from_spec("randint:-5,1").is_err());
+        assert!(distribution_from_spec("const:-5").is_err());
+        assert!(distribution_from_spec("const:NaN").is_err());
+        assert!(distribution_from_spec("uniform:-5,1").is_err());
+        assert!(distribution_from_spec("empirical:-1=1,2=1").is_err());
+    }
+
+    #[test]
+    fn class_demand() {
+        let classes = vec![
+            DemandClass {
+                weight: 1.0,
+                demand: vec![8.0, 2.0],
+                scale: "uniform:0.5,2".to_string(),
+            },
+            DemandClass {
+                weight: 1.0,
+                demand: vec![1.0, 6.0],
+                scale: "const:1".to_string(),
+            },
+        ];
+        let profile = ClassDemand::new(&classes).unwrap();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        for _ in 0..100 {
+            let demand = profile.sample(2, &mut rng);
+            if demand[0] > demand[1] {
+                // Scaled together, so the ratio of the class is kept.
+                assert!((demand[0] / demand[1] - 4.0).abs() < 1e-9);
+            } else {
+                assert_eq!(demand, [1.0, 6.0]);
+            }
+        }
+        assert!(ClassDemand::new(&[]).is_err());
+        for demand in &[vec![8.0, -2.0], vec![f64::NAN, 1.0], Vec::new()] {
+            let class = DemandClass {
+                weight: 1.0,
+                demand: demand.clone(),
+                scale: "const:1".to_string(),
+            };
+            assert!(ClassDemand::new(&[class]).is_err(), "{:?}", demand);
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// How the simulator advances time.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Engine {
+    /// Steps every packet once per tick. Completions are rounded up to ticks.
+    Tick,
+    /// Jumps from event to event in continuous time. Completion times are
+    /// exact and allocation only runs when a packet starts or completes.
+    Event,
+}
+
+impl Engine {
+    /// Parses `tick` or `event`.
+    pub fn from_spec(spec: &str) -> Result<Engine, String> {
+        match spec.trim() {
+            "tick" => Ok(Engine::Tick),
+            "event" => Ok(Engine::Event),
+            _ => Err(format!("{}: unknown engine", spec)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EventKind {
+    /// Packets of the given tick arrive.
+    Arrival(u64),
+    /// The capacity schedules change the capacity at the start of the given
+    /// tick.
+    Capacity(u64),
+    /// The packet in service closest to completion completes, if the
+    /// allocation `epoch` it was computed under is still in place.
+    Completion { epoch: u64 },
+    /// A new allocation takes effect, or one put off may run, on some
+    /// device, see Reallocator::due().
+    Reallocation,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Event {
+    pub time: f64,
+    pub kind: EventKind,
+    /// Order of insertion, breaks ties between events at the same time.
+    seq: u64,
+}
+
+impl Event {
+    fn is_capacity(&self) -> bool {
+        matches!(self.kind, EventKind::Capacity(_))
+    }
+}
+
+impl PartialEq for Event {
+    fn eq(&self, other: &Event) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Event {}
+
+impl PartialOrd for Event {
+    fn partial_cmp(&self, other: &Event) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Event {
+    // Reversed, so that the max-heap pops the earliest event first.
+    fn cmp(&self, other: &Event) -> Ordering {
+        other
+            .time
+            .total_cmp(&self.time)
+            .then_with(|| self.is_capacity().cmp(&other.is_capacity()))
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+/// Pending events, popped in time order and, at equal times, capacity changes
+/// first, so that packets arriving in the same tick see the new capacity.
+/// Other events at equal times are popped in the order they were pushed.
+#[derive(Default)]
+pub struct EventQueue {
+    heap: BinaryHeap<Event>,
+    next_seq: u64,
+}
+
+impl EventQueue {
+    pub fn new() -> EventQueue {
+        Default::default()
+    }
+
+    pub fn push(&mut self, time: f64, kind: EventKind) {
+        self.heap.push(Event {
+            time,
+            kind,
+            seq: self.next_seq,
+        });
+        self.next_seq += 1;
+    }
+
+    pub fn pop(&mut self) -> Option<Event> {
+        self.heap.pop()
+    }
+
+    /// The event pop() returns next, without removing it.
+    pub fn peek(&self) -> Option<&Event> {
+        self.heap.peek()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order() {
+        let mut events = EventQueue::new();
+        events.push(2.5, EventKind::Completion { epoch: 1 });
+        events.push(1.0, EventKind::Arrival(1));
+        events.push(2.5, EventKind::Arrival(2));
+        events.push(0.0, EventKind::Arrival(0));
+        events.push(1.0, EventKind::Capacity(1));
+        let kinds: Vec<EventKind> = std::iter::from_fn(|| events.pop())
+            .map(|event| event.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                EventKind::Arrival(0),
+                EventKind::Capacity(1),
+                EventKind::Arrival(1),
+                EventKind::Completion { epoch: 1 },
+                EventKind::Arrival(2),
+            ]
+        );
+    }
+
+    #[test]
+    fn engine_from_spec() {
+        assert_eq!(Engine::from_spec("tick"), Ok(Engine::Tick));
+        assert_eq!(Engine::from_spec("event"), Ok(Engine::Event));
+        assert!(Engine::from_spec("fast").is_err());
+    }
+}
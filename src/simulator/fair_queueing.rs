@@ -0,0 +1,200 @@
+use super::{Packet, Scheduler};
+use std::collections::HashMap;
+
+/// Share of `capacity` the request of `pkt` takes on its dominant resource,
+/// i.e. the resource it needs the largest share of.
+pub fn dominant_share(pkt: &Packet, capacity: &[f64]) -> f64 {
+    pkt.resource_req
+        .iter()
+        .zip(capacity)
+        .filter(|(_, capacity)| **capacity > 0.0)
+        .map(|(req, capacity)| req / capacity)
+        .fold(0.0, f64::max)
+}
+
+/// Virtual start and finish tags of a waiting packet.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Tags {
+    start: f64,
+    finish: f64,
+}
+
+/// Virtual time of a fair queueing scheduler. Every packet is tagged when it
+/// arrives: it starts, in virtual time, when the previous packet of its
+/// tenant finishes, or now if that is earlier, and takes its cost to
+/// finish. Virtual time is the start tag of the last packet started.
+struct VirtualClock {
+    virtual_time: f64,
+    /// Finish tag of the last packet of each tenant.
+    finish: Vec<f64>,
+    /// Tags of the waiting packets, by packet ID.
+    tags: HashMap<u64, Tags>,
+}
+
+impl VirtualClock {
+    fn new(num_tenants: usize) -> VirtualClock {
+        VirtualClock {
+            virtual_time: 0.0,
+            finish: vec![0.0; num_tenants],
+            tags: HashMap::new(),
+        }
+    }
+
+    fn tag(&mut self, pkt: &Packet, cost: f64) {
+        if pkt.tenant() >= self.finish.len() {
+            self.finish.resize(pkt.tenant() + 1, 0.0);
+        }
+        let start = f64::max(self.virtual_time, self.finish[pkt.tenant()]);
+        let finish = start + cost;
+        self.finish[pkt.tenant()] = finish;
+        self.tags.insert(pkt.id(), Tags { start, finish });
+    }
+
+    /// Index in `queue` of the packet with the smallest start tag, ties
+    /// broken by finish tag, or the other way around if `by_finish`, then by
+    /// arrival.
+    fn head(&self, queue: &[Packet], by_finish: bool) -> Option<usize> {
+        let key = |pkt: &Packet| {
+            let tags = self.tags[&pkt.id()];
+            if by_finish {
+                (tags.finish, tags.start, pkt.id())
+            } else {
+                (tags.start, tags.finish, pkt.id())
+            }
+        };
+        (0..queue.len()).min_by(|a, b| {
+            let (a, b) = (key(&queue[*a]), key(&queue[*b]));
+            a.0.total_cmp(&b.0)
+                .then(a.1.total_cmp(&b.1))
+                .then(a.2.cmp(&b.2))
+        })
+    }
+
+    fn start(&mut self, pkt: &Packet) {
+        let tags = self.tags.remove(&pkt.id()).unwrap();
+        self.virtual_time = f64::max(self.virtual_time, tags.start);
+    }
+}
+
+/// Dominant Resource Fair Queueing, the packet-level approximation of DRF.
+/// Packets cost their service time at their dominant share of the nominal
+/// capacity, and start in the order of their start tags.
+pub struct Drfq {
+    /// Nominal capacity, which dominant shares are measured against.
+    capacity: Vec<f64>,
+    clock: VirtualClock,
+}
+
+impl Drfq {
+    pub fn new(capacity: Vec<f64>, num_tenants: usize) -> Drfq {
+        Drfq {
+            capacity,
+            clock: VirtualClock::new(num_tenants),
+        }
+    }
+}
+
+impl Scheduler for Drfq {
+    fn enqueue(&mut self, pkt: &Packet) {
+        let cost = pkt.service_time() * dominant_share(pkt, &self.capacity);
+        self.clock.tag(pkt, cost);
+    }
+
+    fn pick(&mut self, queue: &[Packet]) -> Option<usize> {
+        self.clock.head(queue, false)
+    }
+
+    fn start(&mut self, pkt: &Packet) {
+        self.clock.start(pkt);
+    }
+}
+
+/// Weighted fair queueing on a single `resource`, with every tenant weighted
+/// the same. Packets cost their service time at their share of the nominal
+/// capacity of that resource, whatever else they need, and start in the
+/// order of their finish tags.
+pub struct Wfq {
+    resource: usize,
+    capacity: f64,
+    clock: VirtualClock,
+}
+
+impl Wfq {
+    pub fn new(resource: usize, capacity: &[f64], num_tenants: usize) -> Wfq {
+        Wfq {
+            resource,
+            capacity: capacity[resource],
+            clock: VirtualClock::new(num_tenants),
+        }
+    }
+}
+
+impl Scheduler for Wfq {
+    fn enqueue(&mut self, pkt: &Packet) {
+        let share = if self.capacity > 0.0 {
+            pkt.resource_req[self.resource] / self.capacity
+        } else {
+            0.0
+        };
+        self.clock.tag(pkt, pkt.service_time() * share);
+    }
+
+    fn pick(&mut self, queue: &[Packet]) -> Option<usize> {
+        self.clock.head(queue, true)
+    }
+
+    fn start(&mut self, pkt: &Packet) {
+        self.clock.start(pkt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::scheduler;
+
+    /// Order in which `scheduler` starts packets that can only run one at a
+    /// time: tenant 0 sends three packets mostly needing the first resource
+    /// before tenant 1 sends one needing all of the second.
+    fn order(scheduler: &mut dyn Scheduler) -> Vec<u64> {
+        let mut queue: Vec<Packet> = [
+            (0, [2.0, 1.0]),
+            (0, [2.0, 1.0]),
+            (0, [2.0, 1.0]),
+            (1, [0.0, 4.0]),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(id, (tenant, req))| {
+            Packet::new(id as u64, 0, 1.0, req.to_vec()).with_tenant(*tenant)
+        })
+        .collect();
+        for pkt in &queue {
+            scheduler.enqueue(pkt);
+        }
+        let mut in_service = Vec::new();
+        let mut order = Vec::new();
+        while !queue.is_empty() {
+            assert_eq!(
+                scheduler::fill(scheduler, &mut queue, &mut in_service, &[2.0, 4.0], None),
+                1
+            );
+            order.push(in_service.pop().unwrap().id());
+        }
+        order
+    }
+
+    #[test]
+    fn drfq() {
+        // Both tenants' packets take their whole dominant resource, so
+        // tenant 1 goes second although it arrived last.
+        assert_eq!(order(&mut Drfq::new(vec![2.0, 4.0], 2)), [0, 3, 1, 2]);
+    }
+
+    #[test]
+    fn wfq() {
+        // On the second resource alone, tenant 0's packets look four times
+        // cheaper than tenant 1's.
+        assert_eq!(order(&mut Wfq::new(1, &[2.0, 4.0], 2)), [0, 1, 2, 3]);
+    }
+}
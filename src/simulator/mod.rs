@@ -1,3 +1,35 @@
+pub use self::admission::*;
+pub use self::arrival::*;
+pub use self::capacity::*;
+pub use self::distribution::*;
+pub use self::event::*;
+pub use self::fair_queueing::*;
+pub use self::observer::*;
 pub use self::packet::*;
+pub use self::pipeline::StageOccupancy;
+pub use self::placement::*;
+pub use self::reallocation::*;
+pub use self::scenario::*;
+pub use self::scheduler::*;
+pub use self::simulation::*;
+pub use self::slots::*;
+pub use self::trace::*;
+pub use self::workload::*;
 
+pub mod admission;
+pub mod arrival;
+pub mod capacity;
+pub mod distribution;
+pub mod event;
+pub mod fair_queueing;
+pub mod observer;
 pub mod packet;
+pub mod pipeline;
+pub mod placement;
+pub mod reallocation;
+pub mod scenario;
+pub mod scheduler;
+pub mod simulation;
+pub mod slots;
+pub mod trace;
+pub mod workload;
@@ -0,0 +1,151 @@
+use super::{BufferOccupancy, DropReason, Packet, TraceRecord};
+use crate::resource::{format_named, Resource};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// Outcome of an allocation decision, in the order of the packets in
+/// service.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Allocation {
+    /// Fraction of its demand each packet got.
+    pub coeffs: Vec<f64>,
+    /// Resources each packet got.
+    pub allocs: Vec<Vec<f64>>,
+}
+
+/// Instrumentation attached to a Simulator. Every callback does nothing by
+/// default, so observers only implement what they need.
+pub trait SimObserver {
+    /// A packet arrived in tick `t`, before admission.
+    fn on_arrival(&mut self, _t: u64, _pkt: &TraceRecord) {}
+
+    /// A packet arriving in tick `t` was not admitted.
+    fn on_drop(&mut self, _t: u64, _pkt: &TraceRecord, _reason: DropReason) {}
+
+    /// The packets in service `pkts` on `device` were allocated `allocation`
+    /// of its `capacity` at time `t`.
+    fn on_allocation(
+        &mut self,
+        _t: f64,
+        _device: usize,
+        _capacity: &[f64],
+        _pkts: &[Packet],
+        _allocation: &Allocation,
+    ) {
+    }
+
+    /// The capacity of `device` changed to `capacity` at the start of tick
+    /// `t`.
+    fn on_capacity(&mut self, _t: u64, _device: usize, _capacity: &[f64]) {}
+
+    /// Tick `t` was handled: its arrivals were admitted and, under the tick
+    /// engine, every packet was stepped. The event engine tells of every tick
+    /// once it has handled the events in it.
+    fn on_tick(&mut self, _t: u64, _in_service: &[Packet], _waiting: &[Packet]) {}
+
+    /// `pkt` completed at time `t`.
+    fn on_completion(&mut self, _t: f64, _pkt: &Packet) {}
+
+    /// The run ended.
+    fn on_finish(&mut self) {}
+}
+
+/// Prints arrivals, drops, capacity changes and allocation inputs as they
+/// happen, naming resources.
+pub struct PrintObserver {
+    resources: Vec<Resource>,
+}
+
+impl PrintObserver {
+    pub fn new(resources: Vec<Resource>) -> PrintObserver {
+        PrintObserver { resources }
+    }
+}
+
+impl SimObserver for PrintObserver {
+    fn on_arrival(&mut self, t: u64, pkt: &TraceRecord) {
+        println!(
+            "t:{}, tenant:{}, size:{}, class:{}, service_time:{}, resource_req:{}",
+            t,
+            pkt.tenant,
+            pkt.size,
+            pkt.class,
+            pkt.service_time,
+            format_named(&self.resources, &pkt.resource_req)
+        );
+    }
+
+    fn on_drop(&mut self, t: u64, pkt: &TraceRecord, reason: DropReason) {
+        println!("t:{}, tenant:{}, dropped:{}", t, pkt.tenant, reason);
+    }
+
+    fn on_capacity(&mut self, t: u64, device: usize, capacity: &[f64]) {
+        println!(
+            "t:{}, device:{}, capacity:{}",
+            t,
+            device,
+            format_named(&self.resources, capacity)
+        );
+    }
+
+    fn on_allocation(
+        &mut self,
+        t: f64,
+        device: usize,
+        capacity: &[f64],
+        pkts: &[Packet],
+        _allocation: &Allocation,
+    ) {
+        let requests: Vec<String> = pkts
+            .iter()
+            .map(|pkt| format_named(&self.resources, &pkt.resource_req))
+            .collect();
+        println!(
+            "t: {}, device: {}, capacity: {} requests: [{}]",
+            t,
+            device,
+            format_named(&self.resources, capacity),
+            requests.join(", ")
+        );
+    }
+}
+
+/// Writes the bytes held on every device in every tick to a .csv file as
+/// `t,device,waiting,in_service` lines, see BufferOccupancy.
+pub struct OccupancyWriter {
+    out: BufWriter<File>,
+    num_devices: usize,
+}
+
+impl OccupancyWriter {
+    pub fn create(path: &str, num_devices: usize) -> io::Result<OccupancyWriter> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "t,device,waiting,in_service")?;
+        Ok(OccupancyWriter { out, num_devices })
+    }
+
+    fn write(&mut self, t: u64, in_service: &[Packet], waiting: &[Packet]) -> io::Result<()> {
+        for device in 0..self.num_devices {
+            let sample = BufferOccupancy::new(t, device, in_service, waiting);
+            writeln!(
+                self.out,
+                "{},{},{},{}",
+                sample.t, sample.device, sample.waiting, sample.in_service
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl SimObserver for OccupancyWriter {
+    fn on_tick(&mut self, t: u64, in_service: &[Packet], waiting: &[Packet]) {
+        self.write(t, in_service, waiting)
+            .unwrap_or_else(|e| panic!("failed to write occupancy: {}", e));
+    }
+
+    fn on_finish(&mut self) {
+        self.out
+            .flush()
+            .unwrap_or_else(|e| panic!("failed to write occupancy: {}", e));
+    }
+}
@@ -1,11 +1,31 @@
+/// Identity of the flow a packet belongs to, unique across tenants.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FlowId {
+    pub tenant: usize,
+    pub app: usize,
+    pub flow: usize,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Packet {
     /// Packet ID
     id: u64,
+    /// Tenant the packet belongs to.
+    tenant: usize,
+    /// Application of the tenant the packet belongs to.
+    app: usize,
+    /// Flow of the application the packet belongs to.
+    flow: usize,
+    /// Device of the cluster the packet was placed on.
+    device: usize,
+    /// Size in bytes, which it holds of every memory resource.
+    size: u64,
+    /// Traffic class, e.g. a DSCP codepoint. The higher, the more urgent.
+    class: u8,
     /// Time t, the packet showed up.
     t_arrival: u64,
     /// Time t, the packet left the hardware
-    t_departure: u64,
+    t_departure: f64,
     /// Resources requested
     pub resource_req: Vec<f64>,
     /// Resources actually allocated, empty if none.
@@ -14,6 +34,11 @@ pub struct Packet {
     service_time: f64,
     /// Actual service time it has gotten so far.
     adjusted_service_time: f64,
+    /// Stage of a pipeline the packet is at, i.e. the only resource it is
+    /// served on, None if it is served on all of them at once.
+    stage: Option<usize>,
+    /// Service time it has gotten at its stage.
+    stage_service_time: f64,
 }
 
 impl Packet {
@@ -21,26 +46,198 @@ impl Packet {
         Packet {
             id,
             t_arrival,
+            t_departure: t_arrival as f64,
             resource_req,
             service_time,
             ..Default::default()
         }
     }
 
-    /// Steps one tick.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn with_tenant(mut self, tenant: usize) -> Packet {
+        self.tenant = tenant;
+        self
+    }
+
+    pub fn tenant(&self) -> usize {
+        self.tenant
+    }
+
+    pub fn with_flow(mut self, app: usize, flow: usize) -> Packet {
+        self.app = app;
+        self.flow = flow;
+        self
+    }
+
+    pub fn app(&self) -> usize {
+        self.app
+    }
+
+    pub fn flow(&self) -> usize {
+        self.flow
+    }
+
+    pub fn flow_id(&self) -> FlowId {
+        FlowId {
+            tenant: self.tenant,
+            app: self.app,
+            flow: self.flow,
+        }
+    }
+
+    pub fn with_device(mut self, device: usize) -> Packet {
+        self.device = device;
+        self
+    }
+
+    pub fn device(&self) -> usize {
+        self.device
+    }
+
+    pub fn with_size(mut self, size: u64) -> Packet {
+        self.size = size;
+        self
+    }
+
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    pub fn with_class(mut self, class: u8) -> Packet {
+        self.class = class;
+        self
+    }
+
+    pub fn class(&self) -> u8 {
+        self.class
+    }
+
+    pub fn service_time(&self) -> f64 {
+        self.service_time
+    }
+
+    /// Serves the packet stage by stage, visiting in order every resource it
+    /// requests some of, each for its service time at full rate.
+    pub fn with_pipeline(mut self) -> Packet {
+        self.stage = Some(self.next_stage_after(0).unwrap_or(0));
+        self
+    }
+
+    pub fn stage(&self) -> Option<usize> {
+        self.stage
+    }
+
+    /// First resource from `resource` on the packet requests some of.
+    fn next_stage_after(&self, resource: usize) -> Option<usize> {
+        (resource..self.resource_req.len()).find(|j| self.resource_req[*j] > 0.0)
+    }
+
+    /// Stage after the current one, None at the last one or outside a
+    /// pipeline.
+    pub fn next_stage(&self) -> Option<usize> {
+        self.stage
+            .and_then(|stage| self.next_stage_after(stage + 1))
+    }
+
+    /// Whether it got all the service of its stage.
+    pub fn is_stage_done(&self) -> bool {
+        self.stage.is_some() && self.stage_service_time >= self.service_time
+    }
+
+    /// Moves on to `stage`, unscheduled until allocated again.
+    pub fn enter_stage(&mut self, stage: usize) {
+        self.stage = Some(stage);
+        self.stage_service_time = 0.0;
+        self.resource_alloc.clear();
+    }
+
+    /// Steps one tick. Packets that are not scheduled only wait.
     pub fn step(&mut self) -> bool {
+        // Time passes whether or not the packet is being served.
+        self.t_departure += 1.0;
+
         if !self.is_scheduled() {
             return false;
         }
 
-        // Update adjusted service time by the fraction of resources it got.
-        let ratio = self.resource_alloc[0] / self.resource_req[0];
-        self.adjusted_service_time += ratio;
+        self.advance(1.0);
+        if self.is_stage_done() && self.next_stage().is_none() {
+            self.adjusted_service_time = self.service_time;
+        }
+        self.is_completed()
+    }
 
-        // Also update t_departure by 1.
-        self.t_departure += 1;
+    /// Smallest fraction it got of any resource it requested, which is the
+    /// service time it gets per unit of time. Zero if not scheduled. Space
+    /// resources are either held in full, which does not limit the rate, or
+    /// not at all, which stops the packet.
+    /// In a pipeline, only the resource of its stage counts.
+    pub fn rate(&self) -> f64 {
+        if !self.is_scheduled() {
+            return 0.0;
+        }
+        if let Some(stage) = self.stage {
+            let req = self.resource_req[stage];
+            return if req > 0.0 {
+                self.resource_alloc[stage] / req
+            } else {
+                1.0
+            };
+        }
+        self.resource_req
+            .iter()
+            .zip(&self.resource_alloc)
+            .filter(|(req, _)| **req > 0.0)
+            .map(|(req, alloc)| alloc / req)
+            .reduce(f64::min)
+            // A packet requesting nothing is never held back.
+            .unwrap_or(1.0)
+    }
 
-        self.is_completed()
+    /// Whether it was allocated all it requested of `resource`.
+    pub fn holds(&self, resource: usize) -> bool {
+        self.is_scheduled() && self.resource_alloc[resource] >= self.resource_req[resource]
+    }
+
+    /// Serves the packet for `dt` units of time at its current allocation.
+    /// Unlike step(), this does not move the departure time.
+    pub fn advance(&mut self, dt: f64) {
+        let service_time = self.rate() * dt;
+        if self.stage.is_none() {
+            self.adjusted_service_time += service_time;
+            return;
+        }
+        self.stage_service_time += service_time;
+    }
+
+    /// Time until the packet completes at its current allocation, or in a
+    /// pipeline its stage, None if it makes no progress.
+    pub fn remaining_time(&self) -> Option<f64> {
+        let rate = self.rate();
+        if rate <= 0.0 {
+            return None;
+        }
+        let served = match self.stage {
+            Some(_) => self.stage_service_time,
+            None => self.adjusted_service_time,
+        };
+        Some(((self.service_time - served) / rate).max(0.0))
+    }
+
+    /// Marks the packet as completed and departed at time `t`. In a
+    /// pipeline, only its stage is, unless it is the last one.
+    pub fn complete(&mut self, t: f64) {
+        if self.stage.is_some() {
+            self.stage_service_time = self.service_time;
+            if self.next_stage().is_some() {
+                return;
+            }
+        }
+        self.adjusted_service_time = self.service_time;
+        self.t_departure = t;
     }
 
     pub fn is_completed(&self) -> bool {
@@ -55,12 +252,16 @@ impl Packet {
         self.resource_alloc = alloc;
     }
 
-    #[allow(dead_code)]
-    /// Returns the number of ticks it actually took to service this packet.
+    /// Resources allocated, empty if none.
+    pub fn allocation(&self) -> &[f64] {
+        &self.resource_alloc
+    }
+
+    /// Returns the time it actually took to service this packet.
     /// Make sure you check whether this packet is completed, using
     /// is_completed().
-    pub fn latency(&self) -> u64 {
-        self.t_departure - self.t_arrival
+    pub fn latency(&self) -> f64 {
+        self.t_departure - self.t_arrival as f64
     }
 }
 
@@ -69,6 +270,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::bool_assert_comparison)]
     fn simple() {
         let mut p = Packet::new(1, 3, 5.0, vec![2.0, 3.0]);
         assert_eq!(p.id, 1);
@@ -85,5 +287,57 @@ mod tests {
             p.step();
         }
         assert!(p.is_completed());
+        assert_eq!(p.latency(), 10.0);
+    }
+
+    #[test]
+    fn latency_includes_waiting() {
+        let mut p = Packet::new(1, 30, 2.0, vec![2.0]);
+        // Waits for 3 ticks before getting served for 2.
+        for _ in 0..3 {
+            assert!(!p.step());
+        }
+        p.allocate(vec![2.0]);
+        assert!(!p.step());
+        assert!(p.step());
+        assert_eq!(p.latency(), 5.0);
+    }
+
+    #[test]
+    fn continuous() {
+        let mut p = Packet::new(1, 2, 6.0, vec![4.0, 2.0]);
+        assert_eq!(p.remaining_time(), None);
+        p.allocate(vec![1.0, 0.5]);
+        assert_eq!(p.rate(), 0.25);
+        assert_eq!(p.remaining_time(), Some(24.0));
+        p.advance(8.0);
+        assert_eq!(p.remaining_time(), Some(16.0));
+        p.allocate(vec![2.0, 1.0]);
+        assert_eq!(p.remaining_time(), Some(8.0));
+        assert!(!p.holds(0));
+        // Without any of its second resource it is stopped.
+        p.allocate(vec![4.0, 0.0]);
+        assert_eq!(p.rate(), 0.0);
+        assert!(p.holds(0) && !p.holds(1));
+        p.complete(18.5);
+        assert!(p.is_completed());
+        assert_eq!(p.latency(), 16.5);
+    }
+
+    #[test]
+    fn pipeline() {
+        let mut p = Packet::new(1, 0, 2.0, vec![4.0, 0.0, 2.0]).with_pipeline();
+        assert_eq!((p.stage(), p.next_stage()), (Some(0), Some(2)));
+        // Only the resource of the stage counts.
+        p.allocate(vec![2.0, 0.0, 0.0]);
+        assert_eq!(p.remaining_time(), Some(4.0));
+        p.advance(4.0);
+        assert!(p.is_stage_done() && !p.is_completed());
+        assert_eq!(p.remaining_time(), Some(0.0));
+        p.enter_stage(2);
+        assert!(!p.is_scheduled() && p.next_stage().is_none());
+        p.allocate(vec![0.0, 0.0, 2.0]);
+        assert!(!p.step() && p.step());
+        assert_eq!(p.latency(), 2.0);
     }
 }
@@ -0,0 +1,214 @@
+use super::Packet;
+use crate::algorithms::Algorithm;
+
+/// Moves packets through a pipeline whose stages are the resources, see
+/// Packet::with_pipeline(). Every stage serves at most `num_slots` packets
+/// at once, starting its waiting packets in the order they reached it. A
+/// packet done with its stage moves on to the next one if it has a free slot
+/// and nothing waits there, or to its queue if fewer than `buffer` packets
+/// wait there; otherwise it is blocked, keeping its slot without being
+/// served, which holds back the packets behind it. The queue of the stage
+/// packets arrive at is only bounded by admission.
+///
+/// `queue` has the packets waiting at every stage, `in_service` those being
+/// served or blocked. Returns the number of packets that started, moved on,
+/// or got blocked.
+pub fn fill(
+    queue: &mut Vec<Packet>,
+    in_service: &mut Vec<Packet>,
+    num_slots: Option<usize>,
+    buffer: Option<usize>,
+) -> usize {
+    let mut moved = 0;
+    // A packet moving on frees a slot the stage before can use, and so on.
+    loop {
+        let mut changed = false;
+        let mut i = 0;
+        while i < in_service.len() {
+            // Packets done with their last stage are about to complete.
+            if !in_service[i].is_stage_done() || in_service[i].next_stage().is_none() {
+                i += 1;
+                continue;
+            }
+            if in_service[i].rate() > 0.0 {
+                in_service[i].allocate(Vec::new());
+                moved += 1;
+            }
+            let next = in_service[i].next_stage().unwrap();
+            let waiting = queue.iter().filter(|pkt| pkt.stage() == Some(next)).count();
+            let busy = in_service
+                .iter()
+                .filter(|pkt| pkt.stage() == Some(next))
+                .count();
+            let starts = waiting == 0 && num_slots.is_none_or(|num_slots| busy < num_slots);
+            if !starts && buffer.is_some_and(|buffer| waiting >= buffer) {
+                i += 1;
+                continue;
+            }
+            let mut pkt = in_service.remove(i);
+            pkt.enter_stage(next);
+            queue.push(pkt);
+            moved += 1;
+            changed = true;
+        }
+
+        let mut i = 0;
+        while i < queue.len() {
+            let stage = queue[i].stage();
+            let busy = in_service.iter().filter(|pkt| pkt.stage() == stage).count();
+            if num_slots.is_none_or(|num_slots| busy < num_slots) {
+                in_service.push(queue.remove(i));
+                moved += 1;
+                changed = true;
+            } else {
+                i += 1;
+            }
+        }
+        if !changed {
+            return moved;
+        }
+    }
+}
+
+/// Coefficients of the packets in service, each stage sharing its resource
+/// among the packets it serves with `alg`. Blocked packets get nothing.
+pub fn allocate(alg: &dyn Algorithm, capacity: &[f64], in_service: &[Packet]) -> Vec<f64> {
+    let mut coeffs = vec![0.0; in_service.len()];
+    for (stage, capacity) in capacity.iter().enumerate() {
+        let mut served: Vec<usize> = Vec::new();
+        for (i, pkt) in in_service.iter().enumerate() {
+            let blocked = pkt.is_stage_done() && pkt.next_stage().is_some();
+            if pkt.stage() != Some(stage) || blocked {
+                continue;
+            }
+            // Only a packet requesting nothing at all is at a stage it does
+            // not need.
+            if pkt.resource_req[stage] <= 0.0 {
+                coeffs[i] = 1.0;
+            } else if *capacity > 0.0 {
+                served.push(i);
+            }
+        }
+        if served.is_empty() {
+            continue;
+        }
+        let demands: Vec<Vec<f64>> = served
+            .iter()
+            .map(|i| vec![in_service[*i].resource_req[stage]])
+            .collect();
+        for (i, coeff) in served.into_iter().zip(alg.allocate(&[*capacity], &demands)) {
+            coeffs[i] = coeff;
+        }
+    }
+    coeffs
+}
+
+/// Packets at a stage of a pipeline.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StageOccupancy {
+    /// Waiting to start the stage.
+    pub waiting: f64,
+    /// Being served.
+    pub serving: f64,
+    /// Done with the stage, but waiting for room in front of the next one.
+    pub blocked: f64,
+}
+
+/// Occupancy of each of `num_stages` stages.
+pub fn occupancy(
+    queue: &[Packet],
+    in_service: &[Packet],
+    num_stages: usize,
+) -> Vec<StageOccupancy> {
+    let mut stages = vec![StageOccupancy::default(); num_stages];
+    for pkt in queue {
+        stages[pkt.stage().unwrap()].waiting += 1.0;
+    }
+    for pkt in in_service {
+        // Packets done with their last stage are about to complete.
+        let stage = &mut stages[pkt.stage().unwrap()];
+        if !pkt.is_stage_done() {
+            stage.serving += 1.0;
+        } else if pkt.next_stage().is_some() {
+            stage.blocked += 1.0;
+        }
+    }
+    stages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::EqualScale;
+
+    /// Serves the packets at `stage` for one unit of time, at full rate.
+    fn serve(in_service: &mut Vec<Packet>, stage: usize) {
+        for pkt in in_service.iter_mut() {
+            if pkt.stage() == Some(stage) {
+                let mut alloc = vec![0.0; pkt.resource_req.len()];
+                alloc[stage] = pkt.resource_req[stage];
+                pkt.allocate(alloc);
+                pkt.step();
+            }
+        }
+        in_service.retain(|pkt| !pkt.is_completed());
+    }
+
+    /// (waiting, serving, blocked) at every stage.
+    fn stages(queue: &[Packet], in_service: &[Packet]) -> Vec<(f64, f64, f64)> {
+        occupancy(queue, in_service, 2)
+            .iter()
+            .map(|stage| (stage.waiting, stage.serving, stage.blocked))
+            .collect()
+    }
+
+    #[test]
+    fn blocking() {
+        // One slot per stage and room for one packet in front of the second.
+        let mut queue: Vec<Packet> = (0..3)
+            .map(|id| Packet::new(id, 0, 1.0, vec![1.0, 1.0]).with_pipeline())
+            .collect();
+        let mut in_service = Vec::new();
+        let refill = |queue: &mut Vec<Packet>, in_service: &mut Vec<Packet>| {
+            fill(queue, in_service, Some(1), Some(1))
+        };
+        assert_eq!(refill(&mut queue, &mut in_service), 1);
+
+        // Packet 0 moves on to the second stage, packet 1 starts the first.
+        serve(&mut in_service, 0);
+        assert_eq!(refill(&mut queue, &mut in_service), 4);
+        assert_eq!(
+            stages(&queue, &in_service),
+            [(1.0, 1.0, 0.0), (0.0, 1.0, 0.0)]
+        );
+
+        // The second stage stalls: packet 1 waits for it, packet 2 starts.
+        serve(&mut in_service, 0);
+        assert_eq!(refill(&mut queue, &mut in_service), 3);
+        assert_eq!(
+            stages(&queue, &in_service),
+            [(0.0, 1.0, 0.0), (1.0, 1.0, 0.0)]
+        );
+
+        // With the buffer full, packet 2 is blocked and holds the first
+        // stage.
+        serve(&mut in_service, 0);
+        assert_eq!(refill(&mut queue, &mut in_service), 1);
+        assert_eq!(
+            stages(&queue, &in_service),
+            [(0.0, 0.0, 1.0), (1.0, 1.0, 0.0)]
+        );
+        assert_eq!(
+            allocate(&EqualScale(1.0), &[1.0, 1.0], &in_service),
+            [1.0, 0.0]
+        );
+
+        // Packet 0 completes, which lets packet 1 start and packet 2 move on.
+        serve(&mut in_service, 1);
+        assert_eq!(refill(&mut queue, &mut in_service), 2);
+        assert_eq!(
+            stages(&queue, &in_service),
+            [(0.0, 0.0, 0.0), (1.0, 1.0, 0.0)]
+        );
+    }
+}
@@ -0,0 +1,203 @@
+/// How a device of the cluster is chosen for a packet among those it fits
+/// on. Packets that fit on no device go to the least loaded one, see
+/// least_loaded().
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlacementPolicy {
+    /// The first device, in order, the packet fits on.
+    FirstFit,
+    /// The device the packet leaves the least free capacity on, summed over
+    /// the resources as shares of the device's capacity.
+    BestFit,
+    /// The device whose free capacity is the closest in shape to the
+    /// packet's demand, as in the best-fit heuristic of DRFH (Wang et al.,
+    /// "Multi-Resource Fair Allocation in Heterogeneous Cloud Computing
+    /// Systems"): demand and free capacity are both divided by their largest
+    /// component, and the device with the smallest sum of differences wins.
+    Drfh,
+}
+
+/// Which device of the cluster packets go to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub policy: PlacementPolicy,
+    /// Place every tenant on the device its first packet goes to, rather
+    /// than every packet on its own.
+    pub per_tenant: bool,
+}
+
+impl Default for Placement {
+    fn default() -> Placement {
+        Placement {
+            policy: PlacementPolicy::FirstFit,
+            per_tenant: false,
+        }
+    }
+}
+
+impl Placement {
+    /// Parses `first_fit`, `best_fit` or `drfh`, placing every packet on its
+    /// own, optionally followed by `:tenant` to place whole tenants.
+    pub fn from_spec(spec: &str) -> Result<Placement, String> {
+        let mut parts = spec.trim().splitn(2, ':');
+        let policy = match parts.next().unwrap() {
+            "first_fit" => PlacementPolicy::FirstFit,
+            "best_fit" => PlacementPolicy::BestFit,
+            "drfh" => PlacementPolicy::Drfh,
+            _ => return Err(format!("{}: unknown placement policy", spec)),
+        };
+        let per_tenant = match parts.next().map(str::trim) {
+            None | Some("packet") => false,
+            Some("tenant") => true,
+            Some(_) => return Err(format!("{}: expected :packet or :tenant", spec)),
+        };
+        Ok(Placement { policy, per_tenant })
+    }
+}
+
+/// Places packets on the devices of a cluster, remembering where tenants
+/// went under per-tenant placement.
+pub struct Placer {
+    placement: Placement,
+    /// Device of every tenant placed so far.
+    tenants: Vec<Option<usize>>,
+}
+
+impl Placer {
+    pub fn new(placement: Placement) -> Placer {
+        Placer {
+            placement,
+            tenants: Vec::new(),
+        }
+    }
+
+    /// Device a packet of `tenant` requesting `req` goes to, given the
+    /// `capacity` of every device and the `load` already placed on it.
+    pub fn place(
+        &mut self,
+        tenant: usize,
+        req: &[f64],
+        capacity: &[Vec<f64>],
+        load: &[Vec<f64>],
+    ) -> usize {
+        assert!(!capacity.is_empty() && capacity.len() == load.len());
+        if capacity.len() == 1 {
+            return 0;
+        }
+        if self.placement.per_tenant {
+            if let Some(Some(device)) = self.tenants.get(tenant) {
+                return *device;
+            }
+        }
+        let free: Vec<Vec<f64>> = capacity
+            .iter()
+            .zip(load)
+            .map(|(capacity, load)| capacity.iter().zip(load).map(|(c, l)| c - l).collect())
+            .collect();
+        let fits = |d: &usize| req.iter().zip(&free[*d]).all(|(r, f)| r <= f);
+        let fitting = (0..capacity.len()).filter(fits);
+        let device = match self.placement.policy {
+            PlacementPolicy::FirstFit => fitting.min(),
+            PlacementPolicy::BestFit => min_by_key(fitting, |d| {
+                free[d]
+                    .iter()
+                    .zip(req)
+                    .zip(&capacity[d])
+                    .filter(|(_, c)| **c > 0.0)
+                    .map(|((f, r), c)| (f - r) / c)
+                    .sum()
+            }),
+            PlacementPolicy::Drfh => min_by_key(fitting, |d| mismatch(req, &free[d])),
+        }
+        .unwrap_or_else(|| least_loaded(req, capacity, load));
+        if self.placement.per_tenant {
+            if tenant >= self.tenants.len() {
+                self.tenants.resize(tenant + 1, None);
+            }
+            self.tenants[tenant] = Some(device);
+        }
+        device
+    }
+}
+
+/// The device with the smallest `key`, the first one on ties.
+fn min_by_key<I, F>(devices: I, key: F) -> Option<usize>
+where
+    I: Iterator<Item = usize>,
+    F: Fn(usize) -> f64,
+{
+    devices
+        .map(|d| (key(d), d))
+        .min_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)))
+        .map(|(_, d)| d)
+}
+
+/// Sum of the differences between `req` and `free`, each divided by its
+/// largest component, so that only their shapes count.
+fn mismatch(req: &[f64], free: &[f64]) -> f64 {
+    let normalize = |v: &[f64]| -> Vec<f64> {
+        let max = v.iter().cloned().fold(0.0, f64::max);
+        v.iter()
+            .map(|x| if max > 0.0 { x / max } else { 0.0 })
+            .collect()
+    };
+    normalize(req)
+        .iter()
+        .zip(normalize(free))
+        .map(|(r, f)| (r - f).abs())
+        .sum()
+}
+
+/// The device whose dominant share would be the smallest with `req` added to
+/// its `load`.
+fn least_loaded(req: &[f64], capacity: &[Vec<f64>], load: &[Vec<f64>]) -> usize {
+    min_by_key(0..capacity.len(), |d| {
+        load[d]
+            .iter()
+            .zip(req)
+            .zip(&capacity[d])
+            .map(|((l, r), c)| if *c > 0.0 { (l + r) / c } else { f64::INFINITY })
+            .fold(0.0, f64::max)
+    })
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_spec() {
+        assert_eq!(Placement::from_spec("first_fit"), Ok(Placement::default()));
+        assert_eq!(
+            Placement::from_spec("drfh:tenant"),
+            Ok(Placement {
+                policy: PlacementPolicy::Drfh,
+                per_tenant: true,
+            })
+        );
+        assert!(Placement::from_spec("worst_fit").is_err());
+        assert!(Placement::from_spec("best_fit:flow").is_err());
+    }
+
+    #[test]
+    fn policies() {
+        // The first device has more free, but mostly of the first resource.
+        let capacity = vec![vec![10.0, 10.0], vec![10.0, 10.0]];
+        let load = vec![vec![0.0, 6.0], vec![6.0, 5.0]];
+        let place = |spec: &str, req: &[f64]| {
+            Placer::new(Placement::from_spec(spec).unwrap()).place(0, req, &capacity, &load)
+        };
+        assert_eq!(place("first_fit", &[1.0, 1.0]), 0);
+        assert_eq!(place("best_fit", &[1.0, 1.0]), 1);
+        assert_eq!(place("drfh", &[1.0, 1.0]), 1);
+        assert_eq!(place("drfh", &[4.0, 1.0]), 0);
+        // Nothing fits, the second device ends up less loaded.
+        assert_eq!(place("first_fit", &[1.0, 5.5]), 1);
+
+        // Tenant 1 stays where its first packet went.
+        let mut placer = Placer::new(Placement::from_spec("first_fit:tenant").unwrap());
+        assert_eq!(placer.place(1, &[1.0, 4.5], &capacity, &load), 1);
+        assert_eq!(placer.place(1, &[1.0, 1.0], &capacity, &load), 1);
+        assert_eq!(placer.place(0, &[1.0, 1.0], &capacity, &load), 0);
+    }
+}
@@ -0,0 +1,363 @@
+use super::Packet;
+use crate::resource::ResourceKind;
+use std::collections::HashMap;
+
+/// What it costs to change the allocation of a device, e.g. to reprogram its
+/// rate limiters, and how often it may change. Every field is off at 0.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReallocationConfig {
+    /// Time a new allocation takes to take effect, rounded up to the next
+    /// tick by the tick engine. Until then, packets keep what they had, and
+    /// packets that had nothing get nothing.
+    pub delay: f64,
+    /// Fraction of what they had packets lose while a new allocation takes
+    /// effect.
+    pub capacity_loss: f64,
+    /// Shortest time between two allocations of a device. Allocations due
+    /// sooner wait until then.
+    pub min_interval: f64,
+    /// New allocations are skipped if no packet's allocation of any resource
+    /// would change by more than this fraction of its demand.
+    pub hysteresis: f64,
+}
+
+impl ReallocationConfig {
+    /// Parses comma separated `delay=x`, `loss=x`, `interval=x` and
+    /// `hysteresis=x`, any of them omitted being 0.
+    pub fn from_spec(spec: &str) -> Result<ReallocationConfig, String> {
+        let mut config = ReallocationConfig::default();
+        for part in spec
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+        {
+            let mut kv = part.splitn(2, '=');
+            let key = kv.next().unwrap().trim();
+            let value = kv
+                .next()
+                .ok_or_else(|| format!("{}: expected key=value", part))?
+                .trim()
+                .parse::<f64>()
+                .map_err(|e| format!("{}: {}", part, e))?;
+            if value < 0.0 {
+                return Err(format!("{}: must not be negative", part));
+            }
+            match key {
+                "delay" => config.delay = value,
+                "loss" if value <= 1.0 => config.capacity_loss = value,
+                "loss" => return Err(format!("{}: loss is a fraction", part)),
+                "interval" => config.min_interval = value,
+                "hysteresis" => config.hysteresis = value,
+                _ => return Err(format!("{}: unknown reallocation setting", part)),
+            }
+        }
+        Ok(config)
+    }
+}
+
+/// How many allocations changed and what they cost, over all devices.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ReallocationStats {
+    /// Allocations put in place.
+    pub reallocations: u64,
+    /// Allocations skipped by hysteresis.
+    pub skipped: u64,
+    /// Allocations put off to keep the minimum interval.
+    pub deferred: u64,
+    /// Time spent waiting for new allocations to take effect.
+    pub overhead: f64,
+    /// Amount of every resource lost in the meantime, times how long.
+    pub lost: Vec<f64>,
+}
+
+/// A new allocation on its way.
+struct Pending {
+    /// When it was decided, or when the first of several decided in a row
+    /// was.
+    since: f64,
+    /// When it takes effect.
+    until: f64,
+    /// Allocation of every packet, by packet ID.
+    allocs: HashMap<u64, Vec<f64>>,
+    /// Amount of every resource lost until it takes effect.
+    lost: Vec<f64>,
+}
+
+/// Decides when the allocation of a device changes, see
+/// ReallocationConfig.
+pub struct Reallocator {
+    config: ReallocationConfig,
+    /// Time of the last allocation put in place, None before the first.
+    last: Option<f64>,
+    /// Whether an allocation waits for the minimum interval.
+    deferred: bool,
+    pending: Option<Pending>,
+}
+
+impl Reallocator {
+    pub fn new(config: ReallocationConfig) -> Reallocator {
+        Reallocator {
+            config,
+            last: None,
+            deferred: false,
+            pending: None,
+        }
+    }
+
+    /// Next time something is due, None if nothing is.
+    pub fn due(&self) -> Option<f64> {
+        let deferred = self
+            .last
+            .filter(|_| self.deferred)
+            .map(|last| last + self.config.min_interval);
+        let pending = self.pending.as_ref().map(|pending| pending.until);
+        match (deferred, pending) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Whether an allocation at time `t` waits for the minimum interval. If
+    /// so, it is counted in `stats` and due() tells when it may run.
+    pub fn defer(&mut self, t: f64, stats: &mut ReallocationStats) -> bool {
+        let defer = self
+            .last
+            .is_some_and(|last| t < last + self.config.min_interval);
+        if defer {
+            self.deferred = true;
+            stats.deferred += 1;
+        }
+        defer
+    }
+
+    /// Whether an allocation put off by defer() may run at time `t`. The
+    /// caller then allocates again, or calls cancel() if there is nothing to
+    /// allocate.
+    pub fn is_deferred_due(&self, t: f64) -> bool {
+        self.deferred
+            && self
+                .last
+                .is_some_and(|last| t >= last + self.config.min_interval)
+    }
+
+    /// Drops the allocation put off by defer().
+    pub fn cancel(&mut self) {
+        self.deferred = false;
+    }
+
+    /// Puts in place, at time `t`, the new `allocs` of `pkts`, which use
+    /// resources of `kinds` with `capacity`, unless hysteresis skips them.
+    /// Returns whether anything changed.
+    pub fn apply(
+        &mut self,
+        t: f64,
+        pkts: &mut [Packet],
+        allocs: Vec<Vec<f64>>,
+        capacity: &[f64],
+        kinds: &[ResourceKind],
+        stats: &mut ReallocationStats,
+    ) -> bool {
+        self.deferred = false;
+        if self.pending.is_none() && self.is_small_change(pkts, &allocs, capacity, kinds) {
+            stats.skipped += 1;
+            return false;
+        }
+        stats.reallocations += 1;
+        self.last = Some(t);
+        if self.config.delay <= 0.0 {
+            for (pkt, alloc) in pkts.iter_mut().zip(allocs) {
+                pkt.allocate(alloc);
+            }
+            return true;
+        }
+
+        let allocs: HashMap<u64, Vec<f64>> = pkts.iter().map(Packet::id).zip(allocs).collect();
+        let until = t + self.config.delay;
+        if let Some(pending) = &mut self.pending {
+            // Packets already lost what they lose until it takes effect.
+            pending.until = until;
+            pending.allocs = allocs;
+            return false;
+        }
+        let loss = self.config.capacity_loss;
+        let mut lost = vec![0.0; kinds.len()];
+        for pkt in pkts.iter_mut().filter(|pkt| pkt.is_scheduled()) {
+            let alloc: Vec<f64> = pkt
+                .allocation()
+                .iter()
+                .zip(kinds)
+                .map(|(x, kind)| match kind {
+                    ResourceKind::Rate => x * (1.0 - loss),
+                    _ => *x,
+                })
+                .collect();
+            for ((lost, old), new) in lost.iter_mut().zip(pkt.allocation()).zip(&alloc) {
+                *lost += old - new;
+            }
+            pkt.allocate(alloc);
+        }
+        self.pending = Some(Pending {
+            since: t,
+            until,
+            allocs,
+            lost,
+        });
+        loss > 0.0
+    }
+
+    /// Puts in place the new allocation of the packets still in `pkts` if it
+    /// takes effect by time `t`. Returns whether it did.
+    pub fn finish_due(
+        &mut self,
+        t: f64,
+        pkts: &mut [Packet],
+        stats: &mut ReallocationStats,
+    ) -> bool {
+        if !self
+            .pending
+            .as_ref()
+            .is_some_and(|pending| pending.until <= t)
+        {
+            return false;
+        }
+        let mut pending = self.pending.take().unwrap();
+        for pkt in pkts.iter_mut() {
+            if let Some(alloc) = pending.allocs.remove(&pkt.id()) {
+                pkt.allocate(alloc);
+            }
+        }
+        account(&pending, t, stats);
+        true
+    }
+
+    /// Counts the cost of an allocation still on its way at the end of the
+    /// run, at time `t`.
+    pub fn finish(&self, t: f64, stats: &mut ReallocationStats) {
+        if let Some(pending) = &self.pending {
+            account(pending, t.min(pending.until), stats);
+        }
+    }
+
+    /// Whether `allocs` is within the hysteresis of what `pkts` already have,
+    /// which still fits in `capacity`.
+    fn is_small_change(
+        &self,
+        pkts: &[Packet],
+        allocs: &[Vec<f64>],
+        capacity: &[f64],
+        kinds: &[ResourceKind],
+    ) -> bool {
+        if self.config.hysteresis <= 0.0 || !pkts.iter().all(Packet::is_scheduled) {
+            return false;
+        }
+        let fits = (0..kinds.len())
+            .filter(|j| kinds[*j] == ResourceKind::Rate)
+            .all(|j| pkts.iter().map(|pkt| pkt.allocation()[j]).sum::<f64>() <= capacity[j]);
+        fits && pkts.iter().zip(allocs).all(|(pkt, alloc)| {
+            pkt.resource_req
+                .iter()
+                .zip(pkt.allocation())
+                .zip(alloc)
+                .all(|((req, old), new)| (new - old).abs() <= self.config.hysteresis * req)
+        })
+    }
+}
+
+/// Adds the cost of `pending` up to time `end` to `stats`.
+fn account(pending: &Pending, end: f64, stats: &mut ReallocationStats) {
+    let duration = (end - pending.since).max(0.0);
+    stats.overhead += duration;
+    if stats.lost.len() < pending.lost.len() {
+        stats.lost.resize(pending.lost.len(), 0.0);
+    }
+    for (total, lost) in stats.lost.iter_mut().zip(&pending.lost) {
+        *total += lost * duration;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scheduled(allocs: &[f64]) -> Vec<Packet> {
+        allocs
+            .iter()
+            .enumerate()
+            .map(|(id, alloc)| {
+                let mut pkt = Packet::new(id as u64, 0, 1.0, vec![10.0]);
+                pkt.allocate(vec![*alloc]);
+                pkt
+            })
+            .collect()
+    }
+
+    #[test]
+    fn from_spec() {
+        assert_eq!(
+            ReallocationConfig::from_spec("delay=0.5, hysteresis=0.1"),
+            Ok(ReallocationConfig {
+                delay: 0.5,
+                hysteresis: 0.1,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            ReallocationConfig::from_spec(""),
+            Ok(ReallocationConfig::default())
+        );
+        assert!(ReallocationConfig::from_spec("loss=2").is_err());
+        assert!(ReallocationConfig::from_spec("delay").is_err());
+        assert!(ReallocationConfig::from_spec("cost=1").is_err());
+    }
+
+    #[test]
+    fn hysteresis_and_interval() {
+        let kinds = [ResourceKind::Rate];
+        let mut stats = ReallocationStats::default();
+        let mut reallocator = Reallocator::new(ReallocationConfig {
+            min_interval: 2.0,
+            hysteresis: 0.1,
+            ..Default::default()
+        });
+        let mut pkts = scheduled(&[5.0, 5.0]);
+        // Within 10% of the demand of 10.
+        let allocs = vec![vec![4.5], vec![5.5]];
+        assert!(!reallocator.apply(0.0, &mut pkts, allocs, &[10.0], &kinds, &mut stats));
+        assert!(!reallocator.defer(0.0, &mut stats));
+        let allocs = vec![vec![2.0], vec![8.0]];
+        assert!(reallocator.apply(0.0, &mut pkts, allocs, &[10.0], &kinds, &mut stats));
+        assert_eq!(pkts[1].allocation(), [8.0]);
+        assert!(reallocator.defer(1.0, &mut stats));
+        assert_eq!(reallocator.due(), Some(2.0));
+        assert!(!reallocator.is_deferred_due(1.5) && reallocator.is_deferred_due(2.0));
+        assert_eq!(
+            (stats.reallocations, stats.skipped, stats.deferred),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn delay() {
+        let kinds = [ResourceKind::Rate];
+        let mut stats = ReallocationStats::default();
+        let mut reallocator = Reallocator::new(ReallocationConfig {
+            delay: 2.0,
+            capacity_loss: 0.5,
+            ..Default::default()
+        });
+        // Packet 1 just started and gets nothing until the new allocation
+        // takes effect, while packet 0 runs on half of what it had.
+        let mut pkts = scheduled(&[10.0]);
+        pkts.push(Packet::new(1, 0, 1.0, vec![10.0]));
+        let allocs = vec![vec![5.0], vec![5.0]];
+        assert!(reallocator.apply(1.0, &mut pkts, allocs, &[10.0], &kinds, &mut stats));
+        assert_eq!(pkts[0].allocation(), [5.0]);
+        assert!(!pkts[1].is_scheduled());
+        assert_eq!(reallocator.due(), Some(3.0));
+        assert!(!reallocator.finish_due(2.0, &mut pkts, &mut stats));
+        assert!(reallocator.finish_due(3.0, &mut pkts, &mut stats));
+        assert_eq!(pkts[1].allocation(), [5.0]);
+        assert_eq!((stats.overhead, stats.lost.clone()), (2.0, vec![10.0]));
+        assert_eq!(reallocator.due(), None);
+    }
+}
@@ -0,0 +1,433 @@
+use super::distribution::{
+    distribution_from_spec, ClassDemand, DemandClass, DemandProfile, Empirical, IndependentDemand,
+    ValueDistribution,
+};
+use super::Placement;
+use crate::resource::Resource;
+use serde::Deserialize;
+use std::fs;
+use std::io;
+
+/// How the demand vector of a tenant's packets is drawn.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DemandConfig {
+    /// Distribution spec every resource is drawn from independently, see
+    /// distribution_from_spec().
+    Independent(String),
+    /// Packet classes with correlated demands.
+    Classes(Vec<DemandClass>),
+}
+
+impl Default for DemandConfig {
+    fn default() -> DemandConfig {
+        DemandConfig::Independent(DEFAULT_DEMAND.to_string())
+    }
+}
+
+pub const DEFAULT_SERVICE_TIME: &str = "randint:10,20";
+pub const DEFAULT_DEMAND: &str = "randint:1,11";
+pub const DEFAULT_SIZE: &str = "const:1500";
+
+/// The workload of a single tenant.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TenantConfig {
+    /// Arrival process spec, see arrival_process_from_spec().
+    pub arrivals: String,
+    /// Distribution spec of the service time, see distribution_from_spec().
+    #[serde(default = "default_service_time")]
+    pub service_time: String,
+    #[serde(default)]
+    pub demand: DemandConfig,
+    /// Distribution spec of the packet size in bytes, rounded to whole
+    /// bytes. It is the packet's demand of every memory resource, whatever
+    /// `demand` draws for it.
+    #[serde(default = "default_size")]
+    pub size: String,
+    /// Traffic class of the tenant's packets, see Packet::class().
+    #[serde(default)]
+    pub class: u8,
+    /// Applications of the tenant, a single one with a single flow if empty.
+    #[serde(default)]
+    pub apps: Vec<AppConfig>,
+}
+
+fn default_service_time() -> String {
+    DEFAULT_SERVICE_TIME.to_string()
+}
+
+fn default_size() -> String {
+    DEFAULT_SIZE.to_string()
+}
+
+/// An application of a tenant, whose packets are spread evenly over its
+/// flows.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AppConfig {
+    /// Relative frequency of the application among the tenant's packets.
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Number of flows.
+    #[serde(default = "default_flows")]
+    pub flows: usize,
+    /// Demand of the application's packets, the tenant's if omitted.
+    #[serde(default)]
+    pub demand: Option<DemandConfig>,
+    /// Traffic class of the application's packets, the tenant's if omitted.
+    #[serde(default)]
+    pub class: Option<u8>,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_flows() -> usize {
+    1
+}
+
+impl DemandConfig {
+    pub fn profile(&self) -> Result<Box<dyn DemandProfile>, String> {
+        match self {
+            DemandConfig::Independent(spec) => Ok(Box::new(IndependentDemand::new(
+                distribution_from_spec(spec)?,
+            ))),
+            DemandConfig::Classes(classes) => Ok(Box::new(ClassDemand::new(classes)?)),
+        }
+    }
+}
+
+impl TenantConfig {
+    /// A tenant with the given arrivals and the default service time, demand
+    /// and size distributions.
+    pub fn new(arrivals: &str) -> TenantConfig {
+        TenantConfig {
+            arrivals: arrivals.to_string(),
+            service_time: default_service_time(),
+            demand: DemandConfig::default(),
+            size: default_size(),
+            class: 0,
+            apps: Vec::new(),
+        }
+    }
+
+    pub fn service_time_distribution(&self) -> Result<Box<dyn ValueDistribution>, String> {
+        distribution_from_spec(&self.service_time)
+    }
+
+    pub fn size_distribution(&self) -> Result<Box<dyn ValueDistribution>, String> {
+        distribution_from_spec(&self.size)
+    }
+
+    pub fn demand_profile(&self) -> Result<Box<dyn DemandProfile>, String> {
+        self.demand.profile()
+    }
+
+    /// Distribution of the application of a packet, None without
+    /// applications.
+    pub fn app_distribution(&self) -> Result<Option<Empirical>, String> {
+        if self.apps.is_empty() {
+            return Ok(None);
+        }
+        let weights: Vec<f64> = self.apps.iter().map(|app| app.weight).collect();
+        if weights.iter().any(|w| *w < 0.0) || weights.iter().sum::<f64>() <= 0.0 {
+            return Err("app weights must be non-negative and not all 0".to_string());
+        }
+        if self.apps.iter().any(|app| app.flows == 0) {
+            return Err("apps need at least one flow".to_string());
+        }
+        let apps = (0..self.apps.len()).map(|app| app as f64).collect();
+        Ok(Some(Empirical::new(apps, &weights)))
+    }
+
+    /// Demand profile of each application, None for those that use the
+    /// tenant's.
+    pub fn app_demand_profiles(&self) -> Result<Vec<Option<Box<dyn DemandProfile>>>, String> {
+        self.apps
+            .iter()
+            .map(|app| {
+                app.demand
+                    .as_ref()
+                    .map(|demand| demand.profile())
+                    .transpose()
+            })
+            .collect()
+    }
+
+    /// Checks that every demand class of the tenant demands each of
+    /// `num_resources` resources.
+    pub fn check_resources(&self, num_resources: usize) -> Result<(), String> {
+        if let DemandConfig::Classes(classes) = &self.demand {
+            if let Some(class) = classes
+                .iter()
+                .find(|class| class.demand.len() != num_resources)
+            {
+                return Err(format!(
+                    "demand class has {} resources, capacity has {}",
+                    class.demand.len(),
+                    num_resources
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A simulated workload, read from a JSON file such as
+///
+/// ```json
+/// {
+///   "capacity": [10, 20, 65536],
+///   "resources": ["cycles:rate", "sram:space", "buffer:memory"],
+///   "capacity_schedule": ["maintenance:0,500,20,5", "failure:1,4,0.001,0.05"],
+///   "hierarchy": ["drf", "static_partition"],
+///   "tenants": [
+///     {
+///       "arrivals": "poisson:0.5",
+///       "service_time": "exp:15",
+///       "size": "uniform:64,1500",
+///       "apps": [{"weight": 3, "flows": 4}, {"demand": "uniform:1,3", "class": 46}]
+///     },
+///     {
+///       "arrivals": "onoff:2,0,0.1,0.05",
+///       "service_time": "pareto:10,2.5",
+///       "demand": [
+///         {"name": "crypto", "weight": 0.7, "demand": [8, 2, 0], "scale": "lognormal:0,0.3"},
+///         {"name": "memory", "weight": 0.3, "demand": [1, 6, 0]}
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// Omitted service times, demands and sizes fall back to
+/// DEFAULT_SERVICE_TIME, DEFAULT_DEMAND and DEFAULT_SIZE. A cluster gives the
+/// capacity of each of its devices in place of `capacity`, e.g.
+/// `"devices": [[10, 20], [20, 10]]`, and optionally how packets are placed
+/// on them, e.g. `"placement": "drfh"`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Scenario {
+    /// Capacity of each resource, if the scenario fixes it.
+    #[serde(default)]
+    pub capacity: Option<Vec<f64>>,
+    /// Capacity of every device of a cluster, in place of `capacity`.
+    #[serde(default)]
+    pub devices: Vec<Vec<f64>>,
+    /// Placement spec of packets on the devices, see Placement::from_spec().
+    #[serde(default)]
+    pub placement: Option<String>,
+    /// Resource specs, see Resource::from_spec(). All resources are rates if
+    /// omitted.
+    #[serde(default)]
+    pub resources: Option<Vec<String>>,
+    /// Capacity schedule specs, see capacity_schedule_from_spec().
+    #[serde(default)]
+    pub capacity_schedule: Vec<String>,
+    /// Algorithms sharing a tenant's allocation among its applications, then
+    /// an application's among its flows, see Hierarchy. The allocation is
+    /// flat among packets if empty.
+    #[serde(default)]
+    pub hierarchy: Vec<String>,
+    pub tenants: Vec<TenantConfig>,
+}
+
+impl Scenario {
+    pub fn from_file(path: &str) -> io::Result<Scenario> {
+        Scenario::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses a scenario and checks that every spec in it is valid.
+    pub fn parse(text: &str) -> io::Result<Scenario> {
+        let scenario: Scenario = serde_json::from_str(text)?;
+        for (i, tenant) in scenario.tenants.iter().enumerate() {
+            let check = super::arrival_process_from_spec(&tenant.arrivals)
+                .map(|_| ())
+                .and(tenant.service_time_distribution().map(|_| ()))
+                .and(tenant.demand_profile().map(|_| ()))
+                .and(tenant.size_distribution().map(|_| ()))
+                .and(tenant.app_distribution().map(|_| ()))
+                .and(tenant.app_demand_profiles().map(|_| ()))
+                .and(
+                    match scenario.capacity.as_ref().or(scenario.devices.first()) {
+                        Some(capacity) => tenant.check_resources(capacity.len()),
+                        None => Ok(()),
+                    },
+                );
+            if let Err(e) = check {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("tenant {}: {}", i, e),
+                ));
+            }
+        }
+        scenario
+            .resources()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        super::hierarchy_from_levels(&scenario.hierarchy)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        if scenario.capacity.is_some() && !scenario.devices.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "capacity and devices are exclusive",
+            ));
+        }
+        if scenario
+            .devices
+            .iter()
+            .any(|device| device.len() != scenario.devices[0].len())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "devices have different numbers of resources",
+            ));
+        }
+        if let Some(spec) = &scenario.placement {
+            Placement::from_spec(spec)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        }
+        let num_resources = scenario
+            .total_capacity()
+            .map_or(usize::MAX, |capacity| capacity.len());
+        for spec in &scenario.capacity_schedule {
+            if let Err(e) = super::capacity_schedule_from_spec(spec, num_resources) {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+            }
+        }
+        Ok(scenario)
+    }
+
+    /// Capacity of the device, or of the whole cluster, if the scenario
+    /// fixes it.
+    pub fn total_capacity(&self) -> Option<Vec<f64>> {
+        if self.devices.is_empty() {
+            return self.capacity.clone();
+        }
+        Some(
+            (0..self.devices[0].len())
+                .map(|j| self.devices.iter().map(|device| device[j]).sum())
+                .collect(),
+        )
+    }
+
+    /// The resources the scenario names, if any.
+    pub fn resources(&self) -> Result<Option<Vec<Resource>>, String> {
+        match &self.resources {
+            Some(specs) => Ok(Some(
+                specs
+                    .iter()
+                    .map(|spec| Resource::from_spec(spec))
+                    .collect::<Result<Vec<_>, _>>()?,
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::ResourceKind;
+
+    #[test]
+    fn parse() {
+        let scenario = Scenario::parse(
+            r#"{
+                "capacity": [10, 20],
+                "resources": ["cycles", "sram:space"],
+                "capacity_schedule": ["step:100,1,15"],
+                "hierarchy": ["drf"],
+                "tenants": [
+                    {
+                        "arrivals": "poisson:0.5",
+                        "service_time": "exp:15",
+                        "size": "uniform:64,1500",
+                        "class": 10,
+                        "apps": [{"weight": 2, "flows": 3}, {"demand": "uniform:1,2", "class": 46}]
+                    },
+                    {
+                        "arrivals": "bernoulli:0.1",
+                        "demand": [{"weight": 1, "demand": [8, 2], "scale": "uniform:1,2"}]
+                    },
+                    {"arrivals": "periodic:5", "demand": "pareto:1,2"}
+                ]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(scenario.capacity, Some(vec![10.0, 20.0]));
+        assert_eq!(scenario.capacity_schedule, ["step:100,1,15"]);
+        assert_eq!(
+            scenario.resources().unwrap().unwrap()[1],
+            Resource::new("sram", ResourceKind::Space)
+        );
+        assert_eq!(scenario.hierarchy, ["drf"]);
+        assert_eq!(scenario.tenants.len(), 3);
+        assert_eq!(scenario.tenants[0].service_time, "exp:15");
+        assert_eq!(scenario.tenants[0].size, "uniform:64,1500");
+        assert_eq!(scenario.tenants[1].size, DEFAULT_SIZE);
+        assert_eq!(scenario.tenants[0].class, 10);
+        assert_eq!(scenario.tenants[0].apps[1].class, Some(46));
+        let apps = &scenario.tenants[0].apps;
+        assert_eq!((apps[0].weight, apps[0].flows), (2.0, 3));
+        assert_eq!((apps[1].weight, apps[1].flows), (1.0, 1));
+        assert!(apps[0].demand.is_none() && apps[1].demand.is_some());
+        assert!(scenario.tenants[1].apps.is_empty());
+        assert!(matches!(
+            &scenario.tenants[0].demand,
+            DemandConfig::Independent(spec) if spec == DEFAULT_DEMAND
+        ));
+        assert_eq!(scenario.tenants[1].service_time, DEFAULT_SERVICE_TIME);
+        assert!(matches!(
+            &scenario.tenants[1].demand,
+            DemandConfig::Classes(classes) if classes.len() == 1
+        ));
+        assert!(matches!(
+            &scenario.tenants[2].demand,
+            DemandConfig::Independent(spec) if spec == "pareto:1,2"
+        ));
+
+        let cluster = Scenario::parse(
+            r#"{"devices": [[10, 20], [20, 10]], "placement": "drfh:tenant", "tenants": []}"#,
+        )
+        .unwrap();
+        assert_eq!(cluster.total_capacity(), Some(vec![30.0, 30.0]));
+        assert_eq!(scenario.total_capacity(), scenario.capacity);
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Scenario::parse(r#"{"tenants": [{}]}"#).is_err());
+        assert!(Scenario::parse(r#"{"resources": ["sram:disk"], "tenants": []}"#).is_err());
+        assert!(Scenario::parse(r#"{"tenants": [{"arrivals": "poisson"}]}"#).is_err());
+        assert!(Scenario::parse(
+            r#"{"tenants": [{"arrivals": "poisson:1", "service_time": "exp:0"}]}"#
+        )
+        .is_err());
+        assert!(Scenario::parse(
+            r#"{
+                "capacity": [10, 20],
+                "tenants": [{"arrivals": "poisson:1", "demand": [{"weight": 1, "demand": [8]}]}]
+            }"#
+        )
+        .is_err());
+        assert!(Scenario::parse(
+            r#"{"capacity": [1], "capacity_schedule": ["step:1,1,5"], "tenants": []}"#
+        )
+        .is_err());
+        assert!(Scenario::parse(r#"{"hierarchy": ["drf", "drf", "drf"], "tenants": []}"#).is_err());
+        assert!(Scenario::parse(r#"{"hierarchy": ["lottery"], "tenants": []}"#).is_err());
+        assert!(Scenario::parse(r#"{"capacity": [1], "devices": [[1]], "tenants": []}"#).is_err());
+        assert!(Scenario::parse(r#"{"devices": [[1], [1, 2]], "tenants": []}"#).is_err());
+        assert!(
+            Scenario::parse(r#"{"devices": [[1]], "placement": "worst_fit", "tenants": []}"#)
+                .is_err()
+        );
+        assert!(Scenario::parse(
+            r#"{"tenants": [{"arrivals": "poisson:1", "apps": [{"flows": 0}]}]}"#
+        )
+        .is_err());
+        assert!(Scenario::parse(
+            r#"{"tenants": [{"arrivals": "poisson:1", "apps": [{"weight": 0}]}]}"#
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,359 @@
+use super::{dominant_share, Drfq, Packet, Wfq};
+use crate::resource::ResourceKind;
+
+/// How packets in service get the resources.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Execution {
+    /// Every packet waiting for a slot is started, and the allocation
+    /// algorithm shares the resources among them as a fluid.
+    Fluid,
+    /// Packets are started one by one by a packet scheduler, each getting
+    /// all it requests, see fill().
+    Drfq,
+    Mr3,
+    /// Weighted fair queueing on the resource with the given index.
+    Wfq(usize),
+    /// Strict priority by tenant.
+    Priority,
+    /// Strict priority by traffic class.
+    ClassPriority,
+    /// Every resource is a stage of a pipeline, see pipeline::fill(), with at
+    /// most the given number of packets waiting in front of every stage
+    /// packets reach from another, unlimited if None.
+    Pipeline(Option<usize>),
+}
+
+impl Execution {
+    /// Parses `fluid`, `drfq`, `mr3`, `wfq:resource`, `priority`,
+    /// `priority:class`, `pipeline` or `pipeline:buffer`.
+    pub fn from_spec(spec: &str) -> Result<Execution, String> {
+        let mut parts = spec.trim().splitn(2, ':');
+        match (parts.next().unwrap(), parts.next()) {
+            ("fluid", None) => Ok(Execution::Fluid),
+            ("drfq", None) => Ok(Execution::Drfq),
+            ("mr3", None) => Ok(Execution::Mr3),
+            ("wfq", Some(resource)) => resource
+                .trim()
+                .parse::<usize>()
+                .map(Execution::Wfq)
+                .map_err(|e| format!("{}: {}", spec, e)),
+            ("priority", None) => Ok(Execution::Priority),
+            ("priority", Some("class")) => Ok(Execution::ClassPriority),
+            ("pipeline", None) => Ok(Execution::Pipeline(None)),
+            ("pipeline", Some(buffer)) => buffer
+                .trim()
+                .parse::<usize>()
+                .map(|buffer| Execution::Pipeline(Some(buffer)))
+                .map_err(|e| format!("{}: {}", spec, e)),
+            _ => Err(format!("{}: unknown execution mode", spec)),
+        }
+    }
+}
+
+/// A packet scheduler, which decides the order in which waiting packets
+/// start. What fits and how many run at once is up to fill().
+pub trait Scheduler {
+    /// `pkt` arrived and is waiting.
+    fn enqueue(&mut self, pkt: &Packet);
+
+    /// Index in `queue`, in arrival order, of the next packet to start, None
+    /// if nothing waits. Picking again without starting the packet must pick
+    /// it again.
+    fn pick(&mut self, queue: &[Packet]) -> Option<usize>;
+
+    /// The packet last picked starts.
+    fn start(&mut self, _pkt: &Packet) {}
+}
+
+/// Creates the scheduler for `execution`, None under fluid execution and in
+/// a pipeline.
+/// `capacity` is the nominal capacity, and WFQ must be on one of its
+/// resources.
+pub fn scheduler_from_execution(
+    execution: Execution,
+    capacity: &[f64],
+    num_tenants: usize,
+) -> Result<Option<Box<dyn Scheduler>>, String> {
+    Ok(match execution {
+        Execution::Fluid | Execution::Pipeline(_) => None,
+        Execution::Drfq => Some(Box::new(Drfq::new(capacity.to_vec(), num_tenants))),
+        Execution::Mr3 => Some(Box::new(Mr3::new(capacity.to_vec(), num_tenants))),
+        Execution::Wfq(resource) if resource < capacity.len() => {
+            Some(Box::new(Wfq::new(resource, capacity, num_tenants)))
+        }
+        Execution::Wfq(resource) => {
+            return Err(format!(
+                "wfq: resource {} out of {}",
+                resource,
+                capacity.len()
+            ))
+        }
+        Execution::Priority => Some(Box::new(StrictPriority {})),
+        Execution::ClassPriority => Some(Box::new(ClassPriority {})),
+    })
+}
+
+/// Moves packets from `queue` into `in_service` in the order of `scheduler`
+/// while their requests fit in what the packets in service leave of
+/// `capacity`, and at most `num_slots` are in service. The first packet that
+/// does not fit holds back the others, so that the order is kept; a packet
+/// that does not fit even alone starts alone, scaled down by allocate().
+/// Returns the number of packets started.
+pub fn fill(
+    scheduler: &mut dyn Scheduler,
+    queue: &mut Vec<Packet>,
+    in_service: &mut Vec<Packet>,
+    capacity: &[f64],
+    num_slots: Option<usize>,
+) -> usize {
+    let mut used: Vec<f64> = (0..capacity.len())
+        .map(|j| in_service.iter().map(|pkt| pkt.resource_req[j]).sum())
+        .collect();
+    let mut started = 0;
+    while num_slots.is_none_or(|num_slots| in_service.len() < num_slots) {
+        let i = match scheduler.pick(queue) {
+            Some(i) => i,
+            None => break,
+        };
+        let fits = queue[i]
+            .resource_req
+            .iter()
+            .zip(&used)
+            .zip(capacity)
+            .all(|((req, used), capacity)| used + req <= *capacity);
+        if !fits && !in_service.is_empty() {
+            break;
+        }
+        let pkt = queue.remove(i);
+        scheduler.start(&pkt);
+        for (used, req) in used.iter_mut().zip(&pkt.resource_req) {
+            *used += req;
+        }
+        in_service.push(pkt);
+        started += 1;
+    }
+    started
+}
+
+/// Coefficients of the packets started by fill(), and whether they got their
+/// space, as algorithms::allocate_typed() returns them. Every packet gets all
+/// it requests, scaled down evenly if the capacity shrank below what they
+/// were started with. Space is never taken back.
+pub fn allocate(
+    capacity: &[f64],
+    kinds: &[ResourceKind],
+    in_service: &[Packet],
+) -> (Vec<f64>, Vec<bool>) {
+    let scale = (0..capacity.len())
+        .filter(|j| kinds[*j] == ResourceKind::Rate)
+        .map(|j| {
+            let used: f64 = in_service.iter().map(|pkt| pkt.resource_req[j]).sum();
+            if used > 0.0 {
+                capacity[j] / used
+            } else {
+                1.0
+            }
+        })
+        .fold(1.0, f64::min);
+    (vec![scale; in_service.len()], vec![true; in_service.len()])
+}
+
+/// Multi-resource round robin: deficit round robin over dominant shares.
+/// Tenants take turns; on its turn a tenant is credited the largest cost of
+/// any packet so far, a packet costing its service time at its dominant
+/// share of the nominal capacity, and starts its oldest packets while its
+/// credit covers them. Credit left over carries to its next turn, unless it
+/// has nothing waiting.
+pub struct Mr3 {
+    capacity: Vec<f64>,
+    credit: Vec<f64>,
+    quantum: f64,
+    /// Tenant whose turn it is.
+    turn: usize,
+    /// Whether the tenant whose turn it is got its credit for the turn.
+    credited: bool,
+}
+
+impl Mr3 {
+    pub fn new(capacity: Vec<f64>, num_tenants: usize) -> Mr3 {
+        Mr3 {
+            capacity,
+            credit: vec![0.0; num_tenants],
+            quantum: 0.0,
+            turn: 0,
+            credited: false,
+        }
+    }
+
+    fn cost(&self, pkt: &Packet) -> f64 {
+        pkt.service_time() * dominant_share(pkt, &self.capacity)
+    }
+
+    fn next_turn(&mut self) {
+        self.turn = (self.turn + 1) % self.credit.len();
+        self.credited = false;
+    }
+}
+
+impl Scheduler for Mr3 {
+    fn enqueue(&mut self, pkt: &Packet) {
+        if pkt.tenant() >= self.credit.len() {
+            self.credit.resize(pkt.tenant() + 1, 0.0);
+        }
+        self.quantum = f64::max(self.quantum, self.cost(pkt));
+    }
+
+    fn pick(&mut self, queue: &[Packet]) -> Option<usize> {
+        if queue.is_empty() {
+            return None;
+        }
+        loop {
+            let tenant = self.turn;
+            match queue.iter().position(|pkt| pkt.tenant() == tenant) {
+                None => {
+                    self.credit[tenant] = 0.0;
+                    self.next_turn();
+                }
+                Some(i) => {
+                    if self.credit[tenant] >= self.cost(&queue[i]) {
+                        return Some(i);
+                    }
+                    if self.credited {
+                        self.next_turn();
+                    } else {
+                        self.credit[tenant] += self.quantum;
+                        self.credited = true;
+                    }
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, pkt: &Packet) {
+        self.credit[pkt.tenant()] -= self.cost(pkt);
+    }
+}
+
+/// Strict priority: the lower the tenant, the higher its priority. A tenant
+/// only starts packets when no tenant above it has any waiting, oldest
+/// first.
+pub struct StrictPriority {}
+
+impl Scheduler for StrictPriority {
+    fn enqueue(&mut self, _pkt: &Packet) {}
+
+    fn pick(&mut self, queue: &[Packet]) -> Option<usize> {
+        (0..queue.len()).min_by_key(|i| queue[*i].tenant())
+    }
+}
+
+/// Strict priority over traffic classes: the higher the class, the higher
+/// its priority, whatever the tenant. Within a class, oldest first.
+pub struct ClassPriority {}
+
+impl Scheduler for ClassPriority {
+    fn enqueue(&mut self, _pkt: &Packet) {}
+
+    fn pick(&mut self, queue: &[Packet]) -> Option<usize> {
+        (0..queue.len()).min_by_key(|i| u8::MAX - queue[*i].class())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Order in which `scheduler` starts single-tenant packets with the
+    /// given tenants and service times, one at a time.
+    fn order(scheduler: &mut dyn Scheduler, specs: &[(usize, f64)]) -> Vec<u64> {
+        let mut queue: Vec<Packet> = specs
+            .iter()
+            .enumerate()
+            .map(|(id, (tenant, service_time))| {
+                Packet::new(id as u64, 0, *service_time, vec![1.0]).with_tenant(*tenant)
+            })
+            .collect();
+        for pkt in &queue {
+            scheduler.enqueue(pkt);
+        }
+        let mut in_service = Vec::new();
+        let mut order = Vec::new();
+        while !queue.is_empty() {
+            assert_eq!(
+                fill(scheduler, &mut queue, &mut in_service, &[1.0], None),
+                1
+            );
+            order.push(in_service.pop().unwrap().id());
+        }
+        order
+    }
+
+    #[test]
+    fn from_spec() {
+        assert_eq!(Execution::from_spec("wfq:1"), Ok(Execution::Wfq(1)));
+        assert_eq!(Execution::from_spec("mr3"), Ok(Execution::Mr3));
+        assert!(Execution::from_spec("wfq").is_err());
+        assert!(Execution::from_spec("drfq:1").is_err());
+        assert_eq!(
+            Execution::from_spec("pipeline:4"),
+            Ok(Execution::Pipeline(Some(4)))
+        );
+        assert!(Execution::from_spec("pipeline:-1").is_err());
+        assert!(scheduler_from_execution(Execution::Wfq(1), &[1.0], 1).is_err());
+    }
+
+    #[test]
+    fn mr3() {
+        let specs = [(0, 1.0), (0, 1.0), (0, 1.0), (1, 1.0)];
+        assert_eq!(order(&mut Mr3::new(vec![1.0], 2), &specs), [0, 3, 1, 2]);
+        // With a packet twice as long around, every turn is worth two short
+        // packets.
+        let specs = [(0, 1.0), (0, 1.0), (0, 1.0), (1, 2.0)];
+        assert_eq!(order(&mut Mr3::new(vec![1.0], 2), &specs), [0, 1, 3, 2]);
+    }
+
+    #[test]
+    fn priority() {
+        let specs = [(1, 1.0), (0, 1.0), (1, 1.0), (0, 1.0)];
+        assert_eq!(order(&mut StrictPriority {}, &specs), [1, 3, 0, 2]);
+
+        let queue: Vec<Packet> = [0, 46, 10, 46]
+            .iter()
+            .enumerate()
+            .map(|(id, class)| Packet::new(id as u64, 0, 1.0, vec![1.0]).with_class(*class))
+            .collect();
+        assert_eq!(ClassPriority {}.pick(&queue), Some(1));
+        assert_eq!(
+            Execution::from_spec("priority:class"),
+            Ok(Execution::ClassPriority)
+        );
+    }
+
+    #[test]
+    fn slots_and_capacity() {
+        let mut queue: Vec<Packet> = (0..4)
+            .map(|id| Packet::new(id, 0, 1.0, vec![1.0]))
+            .collect();
+        let mut in_service = Vec::new();
+        let mut scheduler = StrictPriority {};
+        assert_eq!(
+            fill(&mut scheduler, &mut queue, &mut in_service, &[3.0], Some(2)),
+            2
+        );
+        assert_eq!(
+            fill(&mut scheduler, &mut queue, &mut in_service, &[3.0], None),
+            1
+        );
+        // The rate resource halved, the space one is kept.
+        let kinds = [ResourceKind::Rate, ResourceKind::Space];
+        let in_service = vec![
+            Packet::new(0, 0, 1.0, vec![2.0, 2.0]),
+            Packet::new(1, 0, 1.0, vec![2.0, 2.0]),
+        ];
+        assert_eq!(
+            allocate(&[4.0, 4.0], &kinds, &in_service),
+            (vec![1.0, 1.0], vec![true, true])
+        );
+        assert_eq!(allocate(&[2.0, 1.0], &kinds, &in_service).0, [0.5, 0.5]);
+    }
+}
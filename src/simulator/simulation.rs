@@ -0,0 +1,1653 @@
+use super::{
+    capacity_schedule_from_spec, pipeline, scheduler, scheduler_from_execution, Admission,
+    Allocation, CapacitySchedule, DropReason, Engine, EventKind, EventQueue, Execution,
+    OccupancyWriter, Packet, Placement, Placer, PrintObserver, QueueConfig, ReallocationConfig,
+    ReallocationStats, Reallocator, Scheduler, SimObserver, SlotPolicy, Slots, StageOccupancy,
+    TenantConfig, TraceRecord, Workload,
+};
+use crate::algorithms::{self, Algorithm, Hierarchy};
+use crate::resource::{format_named, Resource, ResourceKind};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+use std::ops::Range;
+
+/// Everything that determines a single simulation run.
+#[derive(Clone, Debug)]
+pub struct SimConfig {
+    pub algorithm: String,
+    /// Workload of each tenant.
+    pub tenants: Vec<TenantConfig>,
+    /// Nominal capacity of each resource. On a cluster, the capacity of the
+    /// whole cluster as algorithms that weigh resources by their capacity
+    /// see it.
+    pub capacity: Vec<f64>,
+    /// Nominal capacity of every device of a cluster, each with the
+    /// resources of `capacity`. The cluster is a single device of capacity
+    /// `capacity` if empty. Every device has its own capacity schedules,
+    /// queue limits and slots, and allocates among its packets on its own.
+    pub devices: Vec<Vec<f64>>,
+    /// Which device of the cluster packets go to.
+    pub placement: Placement,
+    /// Name and kind of each resource.
+    pub resources: Vec<Resource>,
+    /// Capacity schedule specs applied in order every tick, see
+    /// capacity_schedule_from_spec().
+    pub capacity_schedule: Vec<String>,
+    pub seed: u64,
+    pub ticks: u64,
+    /// Length of a tick in seconds, which throughput is reported against.
+    pub tick_duration: f64,
+    /// Trace to replay packets from, in place of `arrivals` and the random
+    /// packet generator.
+    pub trace: Option<String>,
+    /// Trace to record arriving packets to.
+    pub record: Option<String>,
+    pub queue: QueueConfig,
+    /// Number of processing slots, unlimited if None.
+    pub slots: Option<usize>,
+    pub slot_policy: SlotPolicy,
+    pub engine: Engine,
+    /// How packets in service share the resources. Under a packet scheduler
+    /// the allocation algorithm is not used.
+    pub execution: Execution,
+    /// Algorithms sharing a tenant's allocation among its applications, then
+    /// an application's among its flows, see algorithms::Hierarchy. Packets
+    /// share the resources directly if empty. Only used with fluid
+    /// execution.
+    pub hierarchy: Vec<String>,
+    /// Cost and pace of allocation changes on every device.
+    pub reallocation: ReallocationConfig,
+}
+
+impl SimConfig {
+    /// A run of `ticks` ticks with default queues and slots on the tick
+    /// engine with fluid execution, where every resource is a rate.
+    pub fn new(
+        algorithm: &str,
+        tenants: Vec<TenantConfig>,
+        capacity: Vec<f64>,
+        seed: u64,
+        ticks: u64,
+    ) -> SimConfig {
+        SimConfig {
+            algorithm: algorithm.to_string(),
+            tenants,
+            resources: Resource::defaults(capacity.len()),
+            capacity,
+            devices: Vec::new(),
+            placement: Placement::default(),
+            capacity_schedule: Vec::new(),
+            seed,
+            ticks,
+            tick_duration: DEFAULT_TICK_DURATION,
+            trace: None,
+            record: None,
+            queue: QueueConfig::default(),
+            slots: None,
+            slot_policy: SlotPolicy::Fifo,
+            engine: Engine::Tick,
+            execution: Execution::Fluid,
+            hierarchy: Vec::new(),
+            reallocation: ReallocationConfig::default(),
+        }
+    }
+}
+
+/// Length of a tick in seconds unless a run says otherwise.
+pub const DEFAULT_TICK_DURATION: f64 = 1e-6;
+
+/// Outcome of a single simulation run.
+pub struct SimResult {
+    /// Length of the run in ticks.
+    pub ticks: u64,
+    /// Length of a tick in seconds.
+    pub tick_duration: f64,
+    /// Number of tenants of the workload.
+    pub num_tenants: usize,
+    /// Number of packets that arrived.
+    pub num_pkts: u64,
+    /// Name and kind of each resource.
+    pub resources: Vec<Resource>,
+    /// Packets that completed before the run ended.
+    pub completed: Vec<Packet>,
+    /// Number of dropped packets per tenant and reason.
+    pub drops: BTreeMap<(usize, DropReason), u64>,
+    /// Number of devices of the cluster.
+    pub num_devices: usize,
+    /// Fairness at each level of the hierarchy, tenants first, averaged over
+    /// the allocations where it is defined, see
+    /// HierarchicalAllocation::fairness(). Empty without a hierarchy.
+    pub level_fairness: Vec<Option<f64>>,
+    /// Mean number of packets at each stage of the pipeline, sampled at the
+    /// end of every tick. Empty outside a pipeline.
+    pub stages: Vec<StageOccupancy>,
+    /// Most bytes held on every device at the end of any tick. Empty without
+    /// a memory resource.
+    pub peak_bytes: Vec<u64>,
+    /// How often allocations changed and what it cost.
+    pub reallocation: ReallocationStats,
+}
+
+/// Bytes of the packets on a device in a tick, which is what they hold of
+/// every memory resource.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BufferOccupancy {
+    pub t: u64,
+    pub device: usize,
+    /// Bytes of the packets waiting for a slot.
+    pub waiting: u64,
+    /// Bytes of the packets in service.
+    pub in_service: u64,
+}
+
+impl BufferOccupancy {
+    /// Occupancy of `device` in tick `t` with packets `in_service` and
+    /// `waiting`, of every device.
+    pub fn new(
+        t: u64,
+        device: usize,
+        in_service: &[Packet],
+        waiting: &[Packet],
+    ) -> BufferOccupancy {
+        let bytes = |pkts: &[Packet]| {
+            pkts.iter()
+                .filter(|pkt| pkt.device() == device)
+                .map(Packet::size)
+                .sum()
+        };
+        BufferOccupancy {
+            t,
+            device,
+            waiting: bytes(waiting),
+            in_service: bytes(in_service),
+        }
+    }
+
+    /// Bytes held, waiting or in service.
+    pub fn total(&self) -> u64 {
+        self.waiting + self.in_service
+    }
+}
+
+impl SimResult {
+    pub fn num_drops(&self) -> u64 {
+        self.drops.values().sum()
+    }
+
+    /// Bytes of the packets of `tenant` that completed per tick, of every
+    /// tenant if None.
+    pub fn throughput(&self, tenant: Option<usize>) -> f64 {
+        if self.ticks == 0 {
+            return 0.0;
+        }
+        let bytes: u64 = self
+            .completed
+            .iter()
+            .filter(|pkt| tenant.is_none_or(|tenant| pkt.tenant() == tenant))
+            .map(Packet::size)
+            .sum();
+        bytes as f64 / self.ticks as f64
+    }
+
+    /// Mean latency of the completed packets, None if none completed.
+    pub fn mean_latency(&self) -> Option<f64> {
+        if self.completed.is_empty() {
+            return None;
+        }
+        let total: f64 = self.completed.iter().map(|pkt| pkt.latency()).sum();
+        Some(total / self.completed.len() as f64)
+    }
+}
+
+impl fmt::Display for SimResult {
+    /// Lists the completed packets, then the arrived and dropped packets, the
+    /// throughput of every tenant, the
+    /// completed packets on each device of a cluster, the peak bytes held on
+    /// each device, how often allocations changed, the occupancy of each stage
+    /// of a pipeline and the fairness at each level of the hierarchy.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for pkt in &self.completed {
+            writeln!(f, "{:?}", pkt)?;
+        }
+        writeln!(f, "{}: Total number of packets", self.num_pkts)?;
+        writeln!(f, "{}: Total number of dropped packets", self.num_drops())?;
+        writeln!(
+            f,
+            "{}: Throughput in bytes/s",
+            self.throughput(None) / self.tick_duration
+        )?;
+        for tenant in 0..self.num_tenants {
+            writeln!(
+                f,
+                "tenant:{}, throughput:{} bytes/s",
+                tenant,
+                self.throughput(Some(tenant)) / self.tick_duration
+            )?;
+        }
+        for ((tenant, reason), count) in &self.drops {
+            writeln!(f, "tenant:{}, reason:{}, dropped:{}", tenant, reason, count)?;
+        }
+        if self.num_devices > 1 {
+            for device in 0..self.num_devices {
+                let completed = self
+                    .completed
+                    .iter()
+                    .filter(|pkt| pkt.device() == device)
+                    .count();
+                writeln!(f, "{}: Completed packets on device {}", completed, device)?;
+            }
+        }
+        for (device, peak) in self.peak_bytes.iter().enumerate() {
+            writeln!(f, "{}: Peak bytes held on device {}", peak, device)?;
+        }
+        let reallocation = &self.reallocation;
+        writeln!(
+            f,
+            "{}/{}/{}: Allocations made/skipped/deferred",
+            reallocation.reallocations, reallocation.skipped, reallocation.deferred
+        )?;
+        if reallocation.overhead > 0.0 {
+            writeln!(
+                f,
+                "{}: Time spent reallocating, losing {}",
+                reallocation.overhead,
+                format_named(&self.resources, &reallocation.lost)
+            )?;
+        }
+        for (resource, stage) in self.resources.iter().zip(&self.stages) {
+            writeln!(
+                f,
+                "{:.3}/{:.3}/{:.3}: Mean packets waiting/served/blocked at stage {}",
+                stage.waiting, stage.serving, stage.blocked, resource.name
+            )?;
+        }
+        for (level, fairness) in ["tenants", "apps", "flows"]
+            .iter()
+            .zip(&self.level_fairness)
+        {
+            match fairness {
+                Some(fairness) => writeln!(f, "{}: Fairness among {}", fairness, level)?,
+                None => writeln!(f, "-: Fairness among {}", level)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Creates the hierarchy named by `levels`, the algorithms sharing a
+/// tenant's allocation among its applications, then an application's among
+/// its flows. None if `levels` is empty, as packets have no level below flows.
+pub fn hierarchy_from_levels(levels: &[String]) -> Result<Option<Hierarchy>, String> {
+    match levels.len() {
+        0 => Ok(None),
+        1 | 2 => Hierarchy::new(levels).map(Some),
+        _ => Err("hierarchy has at most an app and a flow level".to_string()),
+    }
+}
+
+/// Runs the simulator as described by `config` to the end, printing its
+/// events as they happen if `verbose` and writing the bytes held in every
+/// tick to `occupancy` if given.
+pub fn simulate(
+    config: &SimConfig,
+    verbose: bool,
+    occupancy: Option<&str>,
+) -> Result<SimResult, String> {
+    let mut sim = Simulator::new(config.clone())?;
+    if verbose {
+        sim.add_observer(Box::new(PrintObserver::new(config.resources.clone())));
+    }
+    if let Some(path) = occupancy {
+        let writer = OccupancyWriter::create(path, config.devices.len().max(1))
+            .map_err(|e| format!("failed to write occupancy {}: {}", path, e))?;
+        sim.add_observer(Box::new(writer));
+    }
+    sim.run();
+    Ok(sim.finish())
+}
+
+/// Completions closer than this are treated as simultaneous by the event
+/// engine, absorbing rounding errors.
+const COMPLETION_EPSILON: f64 = 1e-9;
+
+/// A single simulation run, driven step by step. Everything the run needs,
+/// including its optimizer, is owned by the simulator, so runs share nothing
+/// and a sweep creates each one on the worker thread that runs it.
+pub struct Simulator {
+    config: SimConfig,
+    rng: StdRng,
+    workload: Workload,
+    alg: Box<dyn Algorithm>,
+    /// Hierarchy allocated down with `alg` at the top, None if packets share
+    /// the resources directly.
+    hierarchy: Option<Hierarchy>,
+    /// Sum and number of the fairness values at each level of the hierarchy.
+    level_fairness: Vec<(f64, u64)>,
+    /// Sum of the occupancy of each stage of the pipeline over the ticks.
+    stages: Vec<StageOccupancy>,
+    /// Number of ticks sampled so far.
+    num_ticks: u64,
+    /// Most bytes held on every device so far, empty without a memory
+    /// resource.
+    peak_bytes: Vec<u64>,
+    /// Nominal capacity of every device.
+    devices: Vec<Vec<f64>>,
+    /// Capacity schedules of every device.
+    schedules: Vec<Vec<Box<dyn CapacitySchedule>>>,
+    /// Capacity of each resource of every device in the current tick.
+    capacity: Vec<Vec<f64>>,
+    placer: Placer,
+    /// When the allocation of every device changes.
+    reallocators: Vec<Reallocator>,
+    reallocation: ReallocationStats,
+    admission: Admission,
+    /// Processing slots of every device.
+    slots: Vec<Slots>,
+    /// Packet scheduler, None under fluid execution.
+    scheduler: Option<Box<dyn Scheduler>>,
+    /// Packets in a processing slot, sharing the resources, by device.
+    pkts: Vec<Packet>,
+    /// Packets waiting for a processing slot, in arrival order.
+    queue: Vec<Packet>,
+    completed: Vec<Packet>,
+    /// Number of packets that arrived, admitted or not.
+    num_pkts: u64,
+    /// Time up to which the run has been simulated.
+    now: f64,
+    /// Next tick the tick engine runs.
+    next_tick: u64,
+    /// Pending events of the event engine.
+    events: EventQueue,
+    /// Packets of the pending arrival event of the event engine.
+    next_pkts: Vec<TraceRecord>,
+    /// Capacity of every device of the pending capacity event of the event
+    /// engine.
+    next_capacity: Vec<Vec<f64>>,
+    /// Bumped on every allocation of the event engine, which makes pending
+    /// completions stale.
+    epoch: u64,
+    observers: Vec<Box<dyn SimObserver>>,
+}
+
+impl Simulator {
+    /// Creates the run described by `config`, allocating with the algorithm
+    /// it names.
+    pub fn new(config: SimConfig) -> Result<Simulator, String> {
+        let rate_capacity: Vec<f64> = config
+            .capacity
+            .iter()
+            .zip(&config.resources)
+            .filter(|(_, resource)| resource.kind == ResourceKind::Rate)
+            .map(|(capacity, _)| *capacity)
+            .collect();
+        let alg = algorithms::from_name(&config.algorithm, &rate_capacity)
+            .ok_or_else(|| format!("{}: unknown algorithm", config.algorithm))?;
+        Simulator::with_algorithm(config, alg)
+    }
+
+    /// Creates the run described by `config`, allocating with `alg` in place
+    /// of the algorithm it names. `alg` only sees the rate resources, see
+    /// algorithms::allocate_typed().
+    pub fn with_algorithm(config: SimConfig, alg: Box<dyn Algorithm>) -> Result<Simulator, String> {
+        if config.resources.len() != config.capacity.len() {
+            return Err(format!(
+                "{} resources named, capacity has {}",
+                config.resources.len(),
+                config.capacity.len()
+            ));
+        }
+        let workload = Workload::new(
+            &config.tenants,
+            config.capacity.len(),
+            config.trace.as_deref(),
+            config.record.as_deref(),
+        )?;
+        let devices = if config.devices.is_empty() {
+            vec![config.capacity.clone()]
+        } else {
+            config.devices.clone()
+        };
+        if let Some(device) = devices.iter().find(|d| d.len() != config.capacity.len()) {
+            return Err(format!(
+                "device has {} resources, capacity has {}",
+                device.len(),
+                config.capacity.len()
+            ));
+        }
+        let schedules = devices
+            .iter()
+            .map(|_| {
+                config
+                    .capacity_schedule
+                    .iter()
+                    .map(|spec| capacity_schedule_from_spec(spec, config.capacity.len()))
+                    .collect::<Result<Vec<_>, _>>()
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let scheduler =
+            scheduler_from_execution(config.execution, &config.capacity, workload.num_tenants())?;
+        let pipeline = matches!(config.execution, Execution::Pipeline(_));
+        if (scheduler.is_some() || pipeline) && devices.len() > 1 {
+            return Err("packet schedulers and pipelines need a single device".to_string());
+        }
+        if pipeline
+            && config
+                .resources
+                .iter()
+                .any(|resource| resource.kind != ResourceKind::Rate)
+        {
+            return Err("pipeline stages must be rate resources".to_string());
+        }
+        if !config.hierarchy.is_empty() && config.execution != Execution::Fluid {
+            return Err("hierarchy needs fluid execution".to_string());
+        }
+        let hierarchy = hierarchy_from_levels(&config.hierarchy)?;
+        let level_fairness = match &hierarchy {
+            Some(hierarchy) => vec![(0.0, 0); hierarchy.num_levels()],
+            None => Vec::new(),
+        };
+        let mut sim = Simulator {
+            rng: SeedableRng::seed_from_u64(config.seed),
+            admission: Admission::new(config.queue.clone(), workload.num_tenants()).with_memory(
+                (0..config.resources.len())
+                    .filter(|j| config.resources[*j].kind == ResourceKind::Memory)
+                    .collect(),
+            ),
+            workload,
+            alg,
+            hierarchy,
+            level_fairness,
+            stages: if pipeline {
+                vec![StageOccupancy::default(); config.capacity.len()]
+            } else {
+                Vec::new()
+            },
+            num_ticks: 0,
+            peak_bytes: if config
+                .resources
+                .iter()
+                .any(|resource| resource.kind == ResourceKind::Memory)
+            {
+                vec![0; devices.len()]
+            } else {
+                Vec::new()
+            },
+            schedules,
+            capacity: devices.clone(),
+            placer: Placer::new(config.placement),
+            reallocators: devices
+                .iter()
+                .map(|_| Reallocator::new(config.reallocation.clone()))
+                .collect(),
+            reallocation: ReallocationStats::default(),
+            slots: devices
+                .iter()
+                .map(|_| Slots::new(config.slots, config.slot_policy))
+                .collect(),
+            devices,
+            scheduler,
+            pkts: Vec::new(),
+            queue: Vec::new(),
+            completed: Vec::new(),
+            num_pkts: 0,
+            now: 0.0,
+            next_tick: 0,
+            events: EventQueue::new(),
+            next_pkts: Vec::new(),
+            next_capacity: Vec::new(),
+            epoch: 0,
+            observers: Vec::new(),
+            config,
+        };
+        if sim.config.engine == Engine::Event {
+            sim.schedule_capacity(0);
+            sim.schedule_arrival(0);
+        }
+        Ok(sim)
+    }
+
+    /// Registers `observer` to be called back as the run goes. Observers are
+    /// called in the order they were added.
+    pub fn add_observer(&mut self, observer: Box<dyn SimObserver>) {
+        self.observers.push(observer);
+    }
+
+    /// Time up to which the run has been simulated.
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    /// Capacity of each resource of every device in the current tick.
+    pub fn capacity(&self) -> &[Vec<f64>] {
+        &self.capacity
+    }
+
+    /// Packets in service.
+    pub fn in_service(&self) -> &[Packet] {
+        &self.pkts
+    }
+
+    /// Packets waiting for a processing slot, in arrival order.
+    pub fn waiting(&self) -> &[Packet] {
+        &self.queue
+    }
+
+    /// Packets that completed so far, in order of completion.
+    pub fn completed(&self) -> &[Packet] {
+        &self.completed
+    }
+
+    /// Number of packets that arrived so far, admitted or not.
+    pub fn num_pkts(&self) -> u64 {
+        self.num_pkts
+    }
+
+    /// Number of packets dropped so far per tenant and reason.
+    pub fn drops(&self) -> &BTreeMap<(usize, DropReason), u64> {
+        self.admission.drops()
+    }
+
+    /// Time at which the next step ends, None once the run is over.
+    fn next_time(&self) -> Option<f64> {
+        match self.config.engine {
+            Engine::Tick if self.next_tick < self.config.ticks => Some((self.next_tick + 1) as f64),
+            Engine::Tick => None,
+            Engine::Event => self
+                .events
+                .peek()
+                .map(|event| event.time)
+                .filter(|time| *time <= self.config.ticks as f64),
+        }
+    }
+
+    /// Runs one tick under the tick engine, or handles one event under the
+    /// event engine. Returns false, doing nothing, once the run is over.
+    pub fn step(&mut self) -> bool {
+        if self.next_time().is_none() {
+            return false;
+        }
+        match self.config.engine {
+            Engine::Tick => {
+                let t = self.next_tick;
+                self.step_tick(t);
+                self.next_tick += 1;
+                self.now = self.next_tick as f64;
+            }
+            Engine::Event => {
+                let event = self.events.pop().unwrap();
+                self.step_event(event.time, event.kind);
+            }
+        }
+        true
+    }
+
+    /// Runs every step that ends at or before time `t`.
+    pub fn run_until(&mut self, t: f64) {
+        while self.next_time().is_some_and(|next| next <= t) {
+            self.step();
+        }
+        // Nothing happens until the next event, so packets in service can
+        // progress right up to t.
+        let t = t.min(self.config.ticks as f64);
+        if self.config.engine == Engine::Event && t > self.now {
+            self.advance(t);
+        }
+    }
+
+    /// Runs to the end.
+    pub fn run(&mut self) {
+        while self.step() {}
+    }
+
+    /// Ends the run, flushing the recorded trace.
+    pub fn finish(mut self) -> SimResult {
+        self.workload.finish();
+        if self.next_time().is_none() {
+            self.sample_until(self.config.ticks as f64);
+        }
+        for observer in &mut self.observers {
+            observer.on_finish();
+        }
+        for reallocator in &self.reallocators {
+            reallocator.finish(self.now, &mut self.reallocation);
+        }
+        let num_ticks = self.num_ticks.max(1) as f64;
+        SimResult {
+            ticks: self.config.ticks,
+            tick_duration: self.config.tick_duration,
+            num_tenants: self.workload.num_tenants(),
+            num_pkts: self.num_pkts,
+            resources: self.config.resources.clone(),
+            drops: self.admission.drops().clone(),
+            completed: self.completed,
+            num_devices: self.devices.len(),
+            level_fairness: self
+                .level_fairness
+                .iter()
+                .map(|(sum, count)| {
+                    if *count > 0 {
+                        Some(sum / *count as f64)
+                    } else {
+                        None
+                    }
+                })
+                .collect(),
+            stages: self
+                .stages
+                .iter()
+                .map(|stage| StageOccupancy {
+                    waiting: stage.waiting / num_ticks,
+                    serving: stage.serving / num_ticks,
+                    blocked: stage.blocked / num_ticks,
+                })
+                .collect(),
+            peak_bytes: self.peak_bytes,
+            reallocation: self.reallocation,
+        }
+    }
+
+    /// Updates the most bytes held on every device, adds the occupancy of
+    /// every stage of the pipeline and tells observers, once per tick `t`.
+    fn sample(&mut self, t: u64) {
+        for (device, peak) in self.peak_bytes.iter_mut().enumerate() {
+            let held = BufferOccupancy::new(t, device, &self.pkts, &self.queue).total();
+            *peak = (*peak).max(held);
+        }
+        self.num_ticks += 1;
+        for observer in &mut self.observers {
+            observer.on_tick(t, &self.pkts, &self.queue);
+        }
+        if self.stages.is_empty() {
+            return;
+        }
+        let occupancy = pipeline::occupancy(&self.queue, &self.pkts, self.stages.len());
+        for (sum, stage) in self.stages.iter_mut().zip(occupancy) {
+            sum.waiting += stage.waiting;
+            sum.serving += stage.serving;
+            sum.blocked += stage.blocked;
+        }
+    }
+
+    /// Samples every tick before `time` not sampled yet. The event engine
+    /// only changes anything on events, so these ticks all see the current
+    /// state.
+    fn sample_until(&mut self, time: f64) {
+        let end = self.config.ticks.min(time.ceil() as u64);
+        if self.observers.is_empty() && self.stages.is_empty() && self.peak_bytes.is_empty() {
+            self.num_ticks = self.num_ticks.max(end);
+            return;
+        }
+        while self.num_ticks < end {
+            self.sample(self.num_ticks);
+        }
+    }
+
+    /// Capacity of every device in tick `t` under the schedules, which keep
+    /// state between ticks and so must see every tick in order.
+    fn scheduled_capacity(&mut self, t: u64) -> Vec<Vec<f64>> {
+        let mut capacity = self.devices.clone();
+        for (capacity, schedules) in capacity.iter_mut().zip(&mut self.schedules) {
+            for schedule in schedules {
+                schedule.apply(t, capacity, &mut self.rng);
+            }
+        }
+        capacity
+    }
+
+    /// Sets the capacity of every device from tick `t` on. Returns whether
+    /// the capacity of any device changed.
+    fn set_capacity(&mut self, t: u64, capacity: Vec<Vec<f64>>) -> bool {
+        let mut changed = false;
+        for (device, capacity) in capacity.into_iter().enumerate() {
+            if capacity == self.capacity[device] {
+                continue;
+            }
+            self.capacity[device] = capacity;
+            for observer in &mut self.observers {
+                observer.on_capacity(t, device, &self.capacity[device]);
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    /// Applies the capacity schedules for tick `t`. Returns whether the
+    /// capacity of any device changed.
+    fn update_capacity(&mut self, t: u64) -> bool {
+        if self.config.capacity_schedule.is_empty() {
+            return false;
+        }
+        let capacity = self.scheduled_capacity(t);
+        self.set_capacity(t, capacity)
+    }
+
+    /// Schedules a capacity event for the first tick from `t` on in which
+    /// the schedules change the capacity, if any.
+    fn schedule_capacity(&mut self, t: u64) {
+        if self.config.capacity_schedule.is_empty() {
+            return;
+        }
+        for t in t..self.config.ticks {
+            let capacity = self.scheduled_capacity(t);
+            if capacity != self.capacity {
+                self.events.push(t as f64, EventKind::Capacity(t));
+                self.next_capacity = capacity;
+                return;
+            }
+        }
+    }
+
+    /// Schedules an arrival event for the first tick from `t` on in which
+    /// packets arrive, if any.
+    fn schedule_arrival(&mut self, t: u64) {
+        if let Some((t, new_pkts)) =
+            self.workload
+                .next_arrivals(t, self.config.ticks, &mut self.rng)
+        {
+            self.events.push(t as f64, EventKind::Arrival(t));
+            self.next_pkts = new_pkts;
+        }
+    }
+
+    /// Admits `new_pkts`, arriving in tick `t`, into the queue.
+    fn arrive(&mut self, t: u64, new_pkts: Vec<TraceRecord>) {
+        for mut new_pkt in new_pkts {
+            for (j, resource) in self.config.resources.iter().enumerate() {
+                if resource.kind == ResourceKind::Memory {
+                    new_pkt.resource_req[j] = new_pkt.size as f64;
+                }
+            }
+            for observer in &mut self.observers {
+                observer.on_arrival(t, &new_pkt);
+            }
+
+            let id = self.num_pkts;
+            self.num_pkts += 1;
+            let mut load = vec![vec![0.0; self.config.capacity.len()]; self.devices.len()];
+            for pkt in self.pkts.iter().chain(self.queue.iter()) {
+                for (load, req) in load[pkt.device()].iter_mut().zip(&pkt.resource_req) {
+                    *load += req;
+                }
+            }
+            let device =
+                self.placer
+                    .place(new_pkt.tenant, &new_pkt.resource_req, &self.capacity, &load);
+            if let Err(reason) = self.admission.admit(
+                new_pkt.tenant,
+                &new_pkt.resource_req,
+                &self.devices[device],
+                &self.capacity[device],
+                self.pkts
+                    .iter()
+                    .chain(self.queue.iter())
+                    .filter(|pkt| pkt.device() == device),
+                &mut self.rng,
+            ) {
+                for observer in &mut self.observers {
+                    observer.on_drop(t, &new_pkt, reason);
+                }
+                continue;
+            }
+
+            let mut p: Packet = Packet::new(id, t, new_pkt.service_time, new_pkt.resource_req)
+                .with_tenant(new_pkt.tenant)
+                .with_flow(new_pkt.app, new_pkt.flow)
+                .with_device(device)
+                .with_size(new_pkt.size)
+                .with_class(new_pkt.class);
+            if let Execution::Pipeline(_) = self.config.execution {
+                p = p.with_pipeline();
+            }
+            if let Some(scheduler) = &mut self.scheduler {
+                scheduler.enqueue(&p);
+            }
+            self.queue.push(p);
+        }
+    }
+
+    /// Steps every packet through tick `t`.
+    fn step_tick(&mut self, t: u64) {
+        self.reallocate_due(t as f64);
+
+        // Packets in service get their share of the new capacity right away.
+        if self.update_capacity(t) && !self.pkts.is_empty() {
+            self.run_allocation(t as f64);
+        }
+
+        // New packets coming
+        let new_pkts = self.workload.arrivals(t, &mut self.rng);
+        self.arrive(t, new_pkts);
+
+        // Step each packet, waiting packets only age.
+        for pkt in &mut self.queue {
+            pkt.step();
+        }
+        let mut done_pkts = 0;
+        for pkt in &mut self.pkts {
+            let done = pkt.step();
+
+            if done {
+                for observer in &mut self.observers {
+                    observer.on_completion((t + 1) as f64, pkt);
+                }
+                // TODO: Move instead of copy.
+                self.completed.push(pkt.clone());
+                done_pkts += 1;
+            }
+        }
+
+        // Remove packets that are completed.
+        self.pkts.retain(|pkt| !pkt.is_completed());
+
+        // Start waiting packets in the slots that are free.
+        let started_pkts = self.start_waiting();
+
+        // Check whether a new allocation needs to happen
+        if !self.pkts.is_empty() && (started_pkts > 0 || done_pkts > 0) {
+            self.run_allocation(t as f64);
+        }
+
+        self.sample(t);
+    }
+
+    /// Serves packets in service from now until time `t`.
+    fn advance(&mut self, t: f64) {
+        for pkt in &mut self.pkts {
+            pkt.advance(t - self.now);
+        }
+        self.now = t;
+    }
+
+    /// Handles an event of the event engine. Packets in service progress at
+    /// the rate of their allocation, which only changes when a packet starts
+    /// or completes, so the next completion is known exactly and nothing
+    /// needs to happen in between. Packets arriving in tick t arrive at time
+    /// t, capacity changes at the start of their tick, ticks in which
+    /// neither happens get no event, and the run ends at time `ticks`.
+    fn step_event(&mut self, time: f64, kind: EventKind) {
+        self.sample_until(time);
+        self.advance(time);
+
+        let mut done_pkts = 0;
+        let mut capacity_changed = false;
+        let mut reallocated = false;
+        match kind {
+            EventKind::Arrival(t) => {
+                let new_pkts = std::mem::take(&mut self.next_pkts);
+                self.arrive(t, new_pkts);
+                self.schedule_arrival(t + 1);
+            }
+            EventKind::Capacity(t) => {
+                let capacity = std::mem::take(&mut self.next_capacity);
+                capacity_changed = self.set_capacity(t, capacity);
+                self.schedule_capacity(t + 1);
+            }
+            EventKind::Completion { epoch } => {
+                if epoch != self.epoch {
+                    return;
+                }
+                // The event is for the packet closest to completion, which
+                // rounding may have left just short of it.
+                let first = next_completion(&self.pkts).unwrap();
+                for pkt in &mut self.pkts {
+                    if pkt
+                        .remaining_time()
+                        .is_some_and(|r| r <= first + COMPLETION_EPSILON)
+                    {
+                        // In a pipeline, most packets only complete a stage.
+                        pkt.complete(time);
+                        done_pkts += 1;
+                        if !pkt.is_completed() {
+                            continue;
+                        }
+                        for observer in &mut self.observers {
+                            observer.on_completion(time, pkt);
+                        }
+                        self.completed.push(pkt.clone());
+                    }
+                }
+                self.pkts.retain(|pkt| !pkt.is_completed());
+            }
+            EventKind::Reallocation => reallocated = self.reallocate_due(time),
+        }
+
+        let started_pkts = self.start_waiting();
+        if !self.pkts.is_empty() && (started_pkts > 0 || done_pkts > 0 || capacity_changed) {
+            self.run_allocation(time);
+            reallocated = true;
+        }
+        if reallocated {
+            self.epoch += 1;
+            if let Some(dt) = next_completion(&self.pkts) {
+                self.events
+                    .push(time + dt, EventKind::Completion { epoch: self.epoch });
+            }
+        }
+    }
+
+    /// Moves waiting packets into service, returning how many started.
+    fn start_waiting(&mut self) -> usize {
+        if let Some(packet_scheduler) = &mut self.scheduler {
+            return scheduler::fill(
+                packet_scheduler.as_mut(),
+                &mut self.queue,
+                &mut self.pkts,
+                &self.capacity[0],
+                self.config.slots,
+            );
+        }
+        if let Execution::Pipeline(buffer) = self.config.execution {
+            return pipeline::fill(&mut self.queue, &mut self.pkts, self.config.slots, buffer);
+        }
+        if self.slots.len() == 1 {
+            return self.slots[0].fill(&mut self.queue, &mut self.pkts);
+        }
+        // Every device fills its slots from its own packets, which keeps the
+        // packets in service by device.
+        let (mut queue, mut pkts) = (mem::take(&mut self.queue), mem::take(&mut self.pkts));
+        let mut started = 0;
+        for (device, slots) in self.slots.iter_mut().enumerate() {
+            let (mut waiting, rest): (Vec<Packet>, Vec<Packet>) =
+                queue.into_iter().partition(|pkt| pkt.device() == device);
+            queue = rest;
+            let (mut in_service, rest): (Vec<Packet>, Vec<Packet>) =
+                pkts.into_iter().partition(|pkt| pkt.device() == device);
+            pkts = rest;
+            started += slots.fill(&mut waiting, &mut in_service);
+            self.queue.extend(waiting);
+            self.pkts.extend(in_service);
+        }
+        self.queue.sort_by_key(Packet::id);
+        started
+    }
+
+    /// Packets in service of every device, in Simulator::pkts.
+    fn device_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut start = 0;
+        for device in 0..self.devices.len() {
+            let end = start
+                + self.pkts[start..]
+                    .iter()
+                    .take_while(|pkt| pkt.device() == device)
+                    .count();
+            ranges.push(start..end);
+            start = end;
+        }
+        ranges
+    }
+
+    /// Allocates every device among its packets in service.
+    fn run_allocation(&mut self, t: f64) {
+        for (device, pkts) in self.device_ranges().into_iter().enumerate() {
+            if !pkts.is_empty() {
+                self.allocate_device(t, device, pkts);
+            }
+        }
+    }
+
+    /// Puts in place the allocations that take effect by time `t`, and runs
+    /// those put off until then. Returns whether any allocation changed.
+    fn reallocate_due(&mut self, t: f64) -> bool {
+        let mut changed = false;
+        for (device, pkts) in self.device_ranges().into_iter().enumerate() {
+            let due = self.reallocators[device].due();
+            changed |= self.reallocators[device].finish_due(
+                t,
+                &mut self.pkts[pkts.clone()],
+                &mut self.reallocation,
+            );
+            if !self.reallocators[device].is_deferred_due(t) {
+                self.schedule_reallocation(device, due);
+            } else if pkts.is_empty() {
+                self.reallocators[device].cancel();
+            } else {
+                self.allocate_device(t, device, pkts);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Makes the event engine wake up when something is due for the
+    /// allocation of `device`, unless it is still `due` as before.
+    fn schedule_reallocation(&mut self, device: usize, due: Option<f64>) {
+        if self.config.engine != Engine::Event {
+            return;
+        }
+        match self.reallocators[device].due() {
+            Some(time) if Some(time) != due => self.events.push(time, EventKind::Reallocation),
+            _ => {}
+        }
+    }
+
+    /// Allocates `device` among its packets in service, `pkts` in
+    /// Simulator::pkts.
+    fn allocate_device(&mut self, t: f64, device: usize, pkts: Range<usize>) {
+        let due = self.reallocators[device].due();
+        if self.reallocators[device].defer(t, &mut self.reallocation) {
+            self.schedule_reallocation(device, due);
+            return;
+        }
+        let capacity = &self.capacity[device];
+        let mut requests: Vec<Vec<f64>> = Vec::new();
+        for pkt in self.pkts[pkts.clone()].iter() {
+            requests.push(pkt.resource_req.clone());
+        }
+        let kinds: Vec<ResourceKind> = self.config.resources.iter().map(|r| r.kind).collect();
+        // Space is held until the packet completes.
+        let holding: Vec<bool> = self.pkts[pkts.clone()]
+            .iter()
+            .map(|pkt| (0..kinds.len()).all(|j| kinds[j] != ResourceKind::Space || pkt.holds(j)))
+            .collect();
+        let (coeffs, granted) = match (&self.scheduler, &self.hierarchy) {
+            (Some(_), _) => scheduler::allocate(capacity, &kinds, &self.pkts[pkts.clone()]),
+            (None, _) if !self.stages.is_empty() => {
+                let coeffs =
+                    pipeline::allocate(self.alg.as_ref(), capacity, &self.pkts[pkts.clone()]);
+                let granted = vec![true; coeffs.len()];
+                (coeffs, granted)
+            }
+            (None, Some(hierarchy)) => {
+                let alg = self.alg.as_ref();
+                let pkts = &self.pkts[pkts.clone()];
+                let level_fairness = &mut self.level_fairness;
+                let share = |resources: &[f64], demands: &[Vec<f64>], sharing: &[usize]| {
+                    let paths: Vec<Vec<usize>> = sharing
+                        .iter()
+                        .map(|i| vec![pkts[*i].tenant(), pkts[*i].app(), pkts[*i].flow()])
+                        .collect();
+                    let allocation = hierarchy.allocate(alg, resources, demands, &paths);
+                    for (level, (sum, count)) in level_fairness.iter_mut().enumerate() {
+                        if let Some(fairness) = allocation.fairness(level) {
+                            *sum += fairness;
+                            *count += 1;
+                        }
+                    }
+                    allocation.coeffs
+                };
+                algorithms::allocate_typed_with(share, capacity, &kinds, &requests, &holding)
+            }
+            (None, None) => {
+                algorithms::allocate_typed(self.alg.as_ref(), capacity, &kinds, &requests, &holding)
+            }
+        };
+        let mut allocs = Vec::new();
+        for (i, pkt) in self.pkts[pkts.clone()].iter().enumerate() {
+            let stage = pkt.stage();
+            let alloc: Vec<f64> = pkt
+                .resource_req
+                .iter()
+                .zip(&kinds)
+                .enumerate()
+                .map(|(j, (x, kind))| match kind {
+                    // In a pipeline, a packet only gets the resource of its stage.
+                    _ if stage.is_some_and(|stage| stage != j) => 0.0,
+                    ResourceKind::Rate => x * coeffs[i],
+                    ResourceKind::Space if granted[i] => *x,
+                    ResourceKind::Space => 0.0,
+                    // Held since the packet arrived.
+                    ResourceKind::Memory => *x,
+                })
+                .collect();
+            allocs.push(alloc);
+        }
+        let skipped = self.reallocation.skipped;
+        self.reallocators[device].apply(
+            t,
+            &mut self.pkts[pkts.clone()],
+            allocs.clone(),
+            capacity,
+            &kinds,
+            &mut self.reallocation,
+        );
+        // Allocations skipped by hysteresis never happened.
+        if self.reallocation.skipped == skipped {
+            let allocation = Allocation { coeffs, allocs };
+            for observer in &mut self.observers {
+                observer.on_allocation(t, device, capacity, &self.pkts[pkts.clone()], &allocation);
+            }
+        }
+        self.schedule_reallocation(device, due);
+    }
+}
+
+/// Time until the first of `pkts` completes at the current allocation, None
+/// if none of them makes progress.
+fn next_completion(pkts: &[Packet]) -> Option<f64> {
+    pkts.iter()
+        .filter_map(|pkt| pkt.remaining_time())
+        .min_by(f64::total_cmp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithms::EqualScale;
+    use crate::simulator::{DemandClass, DemandConfig, DEFAULT_SIZE};
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// A run where one packet per tenant arrives at time 0 with the given
+    /// service time, each demanding all of a single resource of 10.
+    fn config(engine: Engine, service_times: &[&str]) -> SimConfig {
+        let tenants = service_times
+            .iter()
+            .map(|service_time| TenantConfig {
+                arrivals: "periodic:100".to_string(),
+                service_time: service_time.to_string(),
+                demand: DemandConfig::Independent("const:10".to_string()),
+                size: DEFAULT_SIZE.to_string(),
+                class: 0,
+                apps: Vec::new(),
+            })
+            .collect();
+        SimConfig {
+            engine,
+            ..SimConfig::new("drf", tenants, vec![10.0], 1, 10)
+        }
+    }
+
+    fn simulator(engine: Engine, service_times: &[&str]) -> Simulator {
+        Simulator::with_algorithm(config(engine, service_times), Box::new(EqualScale(1.0))).unwrap()
+    }
+
+    fn latencies(engine: Engine, service_times: &[&str]) -> Vec<f64> {
+        let mut sim = simulator(engine, service_times);
+        sim.run();
+        sim.finish()
+            .completed
+            .iter()
+            .map(|pkt| pkt.latency())
+            .collect()
+    }
+
+    #[test]
+    fn conatiner_ops() {
+        let mut pkts: Vec<Packet> = Vec::new();
+        pkts.push(Packet::new(1, 1, 2.0, vec![1.0, 2.0]));
+        pkts.retain(|pkt| pkt.is_scheduled());
+        assert!(pkts.is_empty());
+    }
+
+    #[test]
+    fn tick_engine() {
+        // Packets wait for the allocation at the end of their arrival tick.
+        assert_eq!(latencies(Engine::Tick, &["const:3"]), [4.0]);
+    }
+
+    #[test]
+    fn event_engine() {
+        assert_eq!(latencies(Engine::Event, &["const:3"]), [3.0]);
+        // Both run at half rate until the first completes at 4, then the
+        // second runs at full rate for its remaining 2.
+        assert_eq!(
+            latencies(Engine::Event, &["const:2", "const:4"]),
+            [4.0, 6.0]
+        );
+        // Nothing completes past the end of the run.
+        assert!(latencies(Engine::Event, &["const:11"]).is_empty());
+    }
+
+    #[test]
+    fn packet_schedulers() {
+        // Packets run one at a time, in the order of the scheduler.
+        let run = |engine: Engine, execution: Execution, service_times: &[&str]| {
+            let config = SimConfig {
+                execution,
+                ..config(engine, service_times)
+            };
+            let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+            sim.run();
+            let latencies: Vec<(usize, f64)> = sim
+                .finish()
+                .completed
+                .iter()
+                .map(|pkt| (pkt.tenant(), pkt.latency()))
+                .collect();
+            latencies
+        };
+        let short_first = ["const:2", "const:4"];
+        assert_eq!(
+            run(Engine::Event, Execution::Drfq, &short_first),
+            [(0, 2.0), (1, 6.0)]
+        );
+        assert_eq!(
+            run(Engine::Tick, Execution::Drfq, &short_first),
+            [(0, 3.0), (1, 7.0)]
+        );
+        // DRFQ starts the packet that finishes first in virtual time, strict
+        // priority the packet of the first tenant.
+        let long_first = ["const:4", "const:2"];
+        assert_eq!(
+            run(Engine::Event, Execution::Drfq, &long_first),
+            [(1, 2.0), (0, 6.0)]
+        );
+        assert_eq!(
+            run(Engine::Event, Execution::Priority, &long_first),
+            [(0, 4.0), (1, 6.0)]
+        );
+        assert!(Simulator::new(SimConfig {
+            execution: Execution::Wfq(1),
+            ..config(Engine::Event, &long_first)
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn run_until() {
+        let mut sim = simulator(Engine::Event, &["const:2", "const:4"]);
+        sim.run_until(3.0);
+        assert_eq!(sim.now(), 3.0);
+        assert_eq!(sim.num_pkts(), 2);
+        assert_eq!(sim.in_service().len(), 2);
+        assert_eq!(sim.in_service()[1].remaining_time(), Some(5.0));
+        sim.run_until(5.0);
+        assert_eq!(sim.completed().len(), 1);
+        assert_eq!(sim.in_service()[0].remaining_time(), Some(1.0));
+        sim.run();
+        assert_eq!(sim.completed().len(), 2);
+        assert!(!sim.step());
+
+        let mut sim = simulator(Engine::Tick, &["const:3"]);
+        assert!(sim.step());
+        assert_eq!(sim.now(), 1.0);
+        assert!(sim.waiting().is_empty());
+        sim.run_until(4.5);
+        assert_eq!(sim.now(), 4.0);
+        assert_eq!(sim.completed().len(), 1);
+    }
+
+    /// Records every callback as a line of text.
+    struct Recorder(Rc<RefCell<Vec<String>>>);
+
+    impl SimObserver for Recorder {
+        fn on_arrival(&mut self, t: u64, pkt: &TraceRecord) {
+            self.0
+                .borrow_mut()
+                .push(format!("arrival {} {}", t, pkt.tenant));
+        }
+
+        fn on_allocation(
+            &mut self,
+            t: f64,
+            _device: usize,
+            _capacity: &[f64],
+            pkts: &[Packet],
+            allocation: &Allocation,
+        ) {
+            assert_eq!(pkts.len(), allocation.allocs.len());
+            self.0
+                .borrow_mut()
+                .push(format!("allocation {} {:?}", t, allocation.coeffs));
+        }
+
+        fn on_tick(&mut self, t: u64, in_service: &[Packet], waiting: &[Packet]) {
+            if t < 2 {
+                self.0.borrow_mut().push(format!(
+                    "tick {} {} {}",
+                    t,
+                    in_service.len(),
+                    waiting.len()
+                ));
+            }
+        }
+
+        fn on_completion(&mut self, t: f64, pkt: &Packet) {
+            self.0
+                .borrow_mut()
+                .push(format!("completion {} {}", t, pkt.tenant()));
+        }
+    }
+
+    #[test]
+    fn observers() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut sim = simulator(Engine::Event, &["const:2", "const:4"]);
+        sim.add_observer(Box::new(Recorder(log.clone())));
+        sim.run();
+        assert_eq!(
+            *log.borrow(),
+            [
+                "arrival 0 0",
+                "arrival 0 1",
+                "allocation 0 [0.5, 0.5]",
+                "tick 0 2 0",
+                "tick 1 2 0",
+                "completion 4 0",
+                "allocation 4 [1.0]",
+                "completion 6 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn capacity_schedule() {
+        for engine in &[Engine::Tick, Engine::Event] {
+            let config = SimConfig {
+                capacity_schedule: vec!["step:1,0,5".to_string()],
+                ..config(*engine, &["const:4"])
+            };
+            let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+            let log = Rc::new(RefCell::new(Vec::new()));
+            sim.add_observer(Box::new(Recorder(log.clone())));
+            sim.run();
+            assert_eq!(sim.capacity(), [vec![5.0]]);
+            assert!(log.borrow().contains(&"allocation 1 [0.5]".to_string()));
+            // The event engine serves at full rate for 1 before the capacity
+            // halves, the tick engine only allocates at the end of tick 0.
+            let latency = sim.completed()[0].latency();
+            match engine {
+                Engine::Tick => assert_eq!(latency, 9.0),
+                Engine::Event => assert_eq!(latency, 7.0),
+            }
+        }
+        let mut config = SimConfig::new("drf", vec![], vec![10.0], 1, 10);
+        config.capacity_schedule = vec!["step:1,1,5".to_string()];
+        assert!(Simulator::new(config).is_err());
+    }
+
+    #[test]
+    fn space_resources() {
+        let tenant = TenantConfig {
+            arrivals: "periodic:100".to_string(),
+            service_time: "const:2".to_string(),
+            demand: DemandConfig::Classes(vec![DemandClass {
+                weight: 1.0,
+                demand: vec![5.0, 6.0],
+                scale: "const:1".to_string(),
+            }]),
+            size: DEFAULT_SIZE.to_string(),
+            class: 0,
+            apps: Vec::new(),
+        };
+        let config = SimConfig {
+            engine: Engine::Event,
+            ..SimConfig::new("drf", vec![tenant; 2], vec![10.0, 10.0], 1, 10)
+        };
+        let latencies = |config: SimConfig| -> Vec<f64> {
+            let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+            sim.run();
+            sim.completed().iter().map(|pkt| pkt.latency()).collect()
+        };
+        // As rates, both packets share the second resource by slowing down.
+        assert_eq!(latencies(config.clone()), [2.4, 2.4]);
+        // Only one packet fits in the space, the other waits for it.
+        let config = SimConfig {
+            resources: vec![
+                Resource::new("cycles", ResourceKind::Rate),
+                Resource::new("sram", ResourceKind::Space),
+            ],
+            ..config
+        };
+        assert_eq!(latencies(config.clone()), [2.0, 4.0]);
+
+        let config = SimConfig {
+            resources: Resource::defaults(1),
+            ..config
+        };
+        assert!(Simulator::new(config).is_err());
+    }
+
+    #[test]
+    fn cluster() {
+        // Both packets would halve the rate of a single device, but each
+        // fills a device of the cluster of its own.
+        assert_eq!(
+            latencies(Engine::Event, &["const:4", "const:4"]),
+            [8.0, 8.0]
+        );
+        let cluster = |engine: Engine| SimConfig {
+            capacity: vec![20.0],
+            devices: vec![vec![10.0], vec![10.0]],
+            ..config(engine, &["const:4", "const:4"])
+        };
+        for engine in &[Engine::Tick, Engine::Event] {
+            let mut sim =
+                Simulator::with_algorithm(cluster(*engine), Box::new(EqualScale(1.0))).unwrap();
+            sim.run();
+            let completed: Vec<(usize, f64)> = sim
+                .completed()
+                .iter()
+                .map(|pkt| (pkt.device(), pkt.latency()))
+                .collect();
+            // The tick engine only allocates at the end of tick 0.
+            let latency = match engine {
+                Engine::Tick => 5.0,
+                Engine::Event => 4.0,
+            };
+            assert_eq!(completed, [(0, latency), (1, latency)]);
+        }
+
+        // Every device runs the capacity schedules on its own.
+        let config = SimConfig {
+            capacity_schedule: vec!["step:1,0,5".to_string()],
+            ..cluster(Engine::Event)
+        };
+        let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+        sim.run_until(2.0);
+        assert_eq!(sim.capacity(), [vec![5.0], vec![5.0]]);
+
+        let config = SimConfig {
+            execution: Execution::Drfq,
+            ..cluster(Engine::Event)
+        };
+        assert!(Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).is_err());
+        let config = SimConfig {
+            devices: vec![vec![10.0, 10.0]],
+            ..cluster(Engine::Event)
+        };
+        assert!(Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).is_err());
+    }
+
+    #[test]
+    fn reallocation() {
+        // Two packets share a resource until the shorter one completes at 4,
+        // then the other one gets all of it for its last 2 of service.
+        let run = |spec: &str| {
+            let config = SimConfig {
+                reallocation: ReallocationConfig::from_spec(spec).unwrap(),
+                ..config(Engine::Event, &["const:2", "const:4"])
+            };
+            let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+            sim.run();
+            let result = sim.finish();
+            let latencies: Vec<f64> = result.completed.iter().map(Packet::latency).collect();
+            (latencies, result.reallocation)
+        };
+        let (latencies, stats) = run("");
+        assert_eq!(latencies, [4.0, 6.0]);
+        assert_eq!(stats.reallocations, 2);
+
+        // Both allocations take 1 to take effect, the second one stalling the
+        // remaining packet.
+        let (latencies, stats) = run("delay=1,loss=1");
+        assert_eq!(latencies, [5.0, 8.0]);
+        assert_eq!((stats.overhead, stats.lost), (2.0, vec![5.0]));
+
+        // Getting all of the resource is within the hysteresis, so the
+        // remaining packet goes on at half rate.
+        let (latencies, stats) = run("hysteresis=1");
+        assert_eq!(latencies, [4.0, 8.0]);
+        assert_eq!((stats.reallocations, stats.skipped), (1, 1));
+
+        // The second allocation waits until 5.
+        let (latencies, stats) = run("interval=5");
+        assert_eq!(latencies, [4.0, 6.5]);
+        assert_eq!((stats.reallocations, stats.deferred), (2, 1));
+    }
+
+    #[test]
+    fn memory() {
+        // Three packets of 1500 bytes arrive at a buffer of 3000 bytes, and
+        // are served one at a time.
+        let config = SimConfig {
+            engine: Engine::Event,
+            slots: Some(1),
+            resources: vec![
+                Resource::new("cycles", ResourceKind::Rate),
+                Resource::new("buffer", ResourceKind::Memory),
+            ],
+            capacity: vec![10.0, 3000.0],
+            ..config(Engine::Event, &["const:2", "const:2", "const:2"])
+        };
+        let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+        let path = std::env::temp_dir().join("denarii_test_occupancy.csv");
+        let path = path.to_str().unwrap();
+        sim.add_observer(Box::new(OccupancyWriter::create(path, 1).unwrap()));
+        sim.run();
+        let result = sim.finish();
+        assert_eq!(result.drops[&(2, DropReason::BufferFull)], 1);
+        let latencies: Vec<f64> = result.completed.iter().map(Packet::latency).collect();
+        assert_eq!(latencies, [2.0, 4.0]);
+        assert_eq!(result.peak_bytes, [3000]);
+        assert_eq!(result.throughput(None), 300.0);
+        assert_eq!(result.throughput(Some(1)), 150.0);
+        // The waiting packet holds its bytes as much as the one in service.
+        let lines: Vec<String> = std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[1], "0,0,1500,1500");
+        assert_eq!(lines[10], "9,0,0,0");
+    }
+
+    #[test]
+    fn pipeline() {
+        // Three packets pass a fast stage then one at half their rate, one at
+        // a time, except the last one, which only needs the fast stage.
+        let tenant = |demand: Vec<f64>| TenantConfig {
+            arrivals: "periodic:100".to_string(),
+            service_time: "const:2".to_string(),
+            demand: DemandConfig::Classes(vec![DemandClass {
+                weight: 1.0,
+                demand,
+                scale: "const:1".to_string(),
+            }]),
+            size: DEFAULT_SIZE.to_string(),
+            class: 0,
+            apps: Vec::new(),
+        };
+        let tenants = vec![
+            tenant(vec![10.0, 10.0]),
+            tenant(vec![10.0, 10.0]),
+            tenant(vec![10.0, 0.0]),
+        ];
+        let run = |buffer: Option<usize>| {
+            let config = SimConfig {
+                engine: Engine::Event,
+                slots: Some(1),
+                queue: QueueConfig {
+                    admission_control: false,
+                    ..QueueConfig::default()
+                },
+                execution: Execution::Pipeline(buffer),
+                ..SimConfig::new("drf", tenants.clone(), vec![10.0, 5.0], 1, 20)
+            };
+            let mut sim = Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).unwrap();
+            sim.run();
+            let result = sim.finish();
+            let mut latencies: Vec<(usize, f64)> = result
+                .completed
+                .iter()
+                .map(|pkt| (pkt.tenant(), pkt.latency()))
+                .collect();
+            latencies.sort_by_key(|(tenant, _)| *tenant);
+            (latencies, result.stages)
+        };
+        // The second packet waits for the slow stage while the third one
+        // goes through the fast one.
+        let (latencies, stages) = run(None);
+        assert_eq!(latencies, [(0, 6.0), (1, 10.0), (2, 6.0)]);
+        assert!(stages[1].waiting > 0.0 && stages[0].blocked == 0.0);
+        // Without room in front of the slow stage, the second packet blocks
+        // the fast one, and the third one waits behind it.
+        let (latencies, stages) = run(Some(0));
+        assert_eq!(latencies, [(0, 6.0), (1, 10.0), (2, 8.0)]);
+        assert!(stages[0].blocked > 0.0);
+
+        let config = SimConfig {
+            execution: Execution::Pipeline(None),
+            resources: vec![
+                Resource::new("cycles", ResourceKind::Rate),
+                Resource::new("sram", ResourceKind::Space),
+            ],
+            ..SimConfig::new("drf", tenants, vec![10.0, 5.0], 1, 20)
+        };
+        assert!(Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).is_err());
+    }
+
+    #[test]
+    fn hierarchy() {
+        let mut tenant = TenantConfig::new("periodic:1");
+        tenant.service_time = "const:4".to_string();
+        tenant.apps = serde_json::from_str(r#"[{"flows": 2}, {"demand": "const:3"}]"#).unwrap();
+        let config = SimConfig {
+            hierarchy: vec![
+                "static_partition".to_string(),
+                "per_resource_max_min".to_string(),
+            ],
+            ..SimConfig::new(
+                "drf",
+                vec![tenant, TenantConfig::new("periodic:2")],
+                vec![10.0],
+                1,
+                20,
+            )
+        };
+        let mut sim = Simulator::with_algorithm(config.clone(), Box::new(EqualScale(1.0))).unwrap();
+        sim.run();
+        let result = sim.finish();
+        assert_eq!(result.level_fairness.len(), 3);
+        assert!(result.level_fairness[0].is_some());
+        // Static partitioning gives both applications of tenant 0 half of
+        // what it got, whatever they demand.
+        assert_eq!(result.level_fairness[1], Some(1.0));
+        assert!(result.level_fairness[2].is_some());
+
+        let flat = Simulator::with_algorithm(
+            SimConfig {
+                hierarchy: Vec::new(),
+                ..config.clone()
+            },
+            Box::new(EqualScale(1.0)),
+        )
+        .unwrap()
+        .finish();
+        assert!(flat.level_fairness.is_empty());
+        let config = SimConfig {
+            execution: Execution::Drfq,
+            ..config
+        };
+        assert!(Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).is_err());
+    }
+
+    #[test]
+    fn unknown_algorithm() {
+        let config = SimConfig::new("lottery", vec![], vec![10.0], 1, 10);
+        assert!(Simulator::new(config).is_err());
+    }
+
+    #[test]
+    fn resource_mismatch() {
+        let tenant = TenantConfig {
+            arrivals: "periodic:1".to_string(),
+            service_time: "const:1".to_string(),
+            demand: DemandConfig::Classes(vec![DemandClass {
+                weight: 1.0,
+                demand: vec![1.0, 2.0],
+                scale: "const:1".to_string(),
+            }]),
+            size: DEFAULT_SIZE.to_string(),
+            class: 0,
+            apps: Vec::new(),
+        };
+        let config = SimConfig::new("drf", vec![tenant], vec![10.0], 1, 10);
+        assert!(Simulator::with_algorithm(config, Box::new(EqualScale(1.0))).is_err());
+    }
+}
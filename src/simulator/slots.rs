@@ -0,0 +1,154 @@
+use super::Packet;
+
+/// Which waiting packet enters service when a processing slot frees up.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SlotPolicy {
+    /// The packet that arrived first.
+    Fifo,
+    /// The packet with the shortest service time, ties broken by arrival.
+    ShortestFirst,
+    /// Tenants take turns, each starting its oldest packet.
+    RoundRobin,
+}
+
+impl SlotPolicy {
+    /// Parses `fifo`, `sjf` or `rr`.
+    pub fn from_spec(spec: &str) -> Result<SlotPolicy, String> {
+        match spec.trim() {
+            "fifo" => Ok(SlotPolicy::Fifo),
+            "sjf" => Ok(SlotPolicy::ShortestFirst),
+            "rr" => Ok(SlotPolicy::RoundRobin),
+            _ => Err(format!("{}: unknown slot policy", spec)),
+        }
+    }
+}
+
+/// The processing units (cores or engines) of the device. Only packets in a
+/// slot are in service and take part in allocation; the rest wait. Packets
+/// keep their slot until they complete.
+pub struct Slots {
+    num_slots: Option<usize>,
+    policy: SlotPolicy,
+    /// Tenant whose turn it is under round robin.
+    next_tenant: usize,
+}
+
+impl Slots {
+    /// `num_slots` of None means every packet is served right away.
+    pub fn new(num_slots: Option<usize>, policy: SlotPolicy) -> Slots {
+        Slots {
+            num_slots,
+            policy,
+            next_tenant: 0,
+        }
+    }
+
+    /// Moves waiting packets from `queue` into `in_service` until every slot
+    /// is taken or nothing waits. `queue` must be in arrival order. Returns
+    /// the number of packets that entered service.
+    pub fn fill(&mut self, queue: &mut Vec<Packet>, in_service: &mut Vec<Packet>) -> usize {
+        let free = match self.num_slots {
+            Some(num_slots) => num_slots.saturating_sub(in_service.len()),
+            None => queue.len(),
+        };
+        let mut started = 0;
+        while started < free && !queue.is_empty() {
+            let i = self.pick(queue);
+            in_service.push(queue.remove(i));
+            started += 1;
+        }
+        started
+    }
+
+    /// Index of the next packet to start, `queue` must not be empty.
+    fn pick(&mut self, queue: &[Packet]) -> usize {
+        match self.policy {
+            SlotPolicy::Fifo => 0,
+            SlotPolicy::ShortestFirst => {
+                let mut best = 0;
+                for (i, pkt) in queue.iter().enumerate() {
+                    if pkt.service_time() < queue[best].service_time() {
+                        best = i;
+                    }
+                }
+                best
+            }
+            SlotPolicy::RoundRobin => {
+                // The oldest packet of the first tenant at or after
+                // next_tenant, wrapping around.
+                let max_tenant = queue.iter().map(|pkt| pkt.tenant()).max().unwrap();
+                let tenant = (self.next_tenant..=max_tenant)
+                    .chain(0..self.next_tenant)
+                    .find(|tenant| queue.iter().any(|pkt| pkt.tenant() == *tenant))
+                    .unwrap();
+                self.next_tenant = tenant + 1;
+                queue.iter().position(|pkt| pkt.tenant() == tenant).unwrap()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn packets(specs: &[(usize, f64)]) -> Vec<Packet> {
+        specs
+            .iter()
+            .enumerate()
+            .map(|(i, (tenant, service_time))| {
+                Packet::new(i as u64, 0, *service_time, vec![1.0]).with_tenant(*tenant)
+            })
+            .collect()
+    }
+
+    fn started(policy: SlotPolicy, num_slots: usize, specs: &[(usize, f64)]) -> Vec<f64> {
+        let mut slots = Slots::new(Some(num_slots), policy);
+        let mut queue = packets(specs);
+        let mut in_service = Vec::new();
+        assert_eq!(
+            slots.fill(&mut queue, &mut in_service),
+            num_slots.min(specs.len())
+        );
+        assert_eq!(queue.len() + in_service.len(), specs.len());
+        in_service.iter().map(|pkt| pkt.service_time()).collect()
+    }
+
+    #[test]
+    fn policies() {
+        let specs = [(0, 5.0), (0, 3.0), (0, 4.0), (1, 9.0), (2, 1.0)];
+        assert_eq!(started(SlotPolicy::Fifo, 2, &specs), [5.0, 3.0]);
+        assert_eq!(started(SlotPolicy::ShortestFirst, 2, &specs), [1.0, 3.0]);
+        assert_eq!(
+            started(SlotPolicy::RoundRobin, 4, &specs),
+            [5.0, 9.0, 1.0, 3.0]
+        );
+    }
+
+    #[test]
+    fn unlimited() {
+        let mut slots = Slots::new(None, SlotPolicy::Fifo);
+        let mut queue = packets(&[(0, 1.0), (1, 2.0)]);
+        let mut in_service = packets(&[(0, 3.0)]);
+        assert_eq!(slots.fill(&mut queue, &mut in_service), 2);
+        assert!(queue.is_empty());
+        assert_eq!(in_service.len(), 3);
+    }
+
+    #[test]
+    fn full() {
+        let mut slots = Slots::new(Some(1), SlotPolicy::Fifo);
+        let mut queue = packets(&[(0, 1.0)]);
+        let mut in_service = packets(&[(0, 3.0)]);
+        assert_eq!(slots.fill(&mut queue, &mut in_service), 0);
+        assert_eq!(queue.len(), 1);
+    }
+
+    #[test]
+    fn policy_from_spec() {
+        assert_eq!(SlotPolicy::from_spec("fifo"), Ok(SlotPolicy::Fifo));
+        assert_eq!(SlotPolicy::from_spec("sjf"), Ok(SlotPolicy::ShortestFirst));
+        assert_eq!(SlotPolicy::from_spec("rr"), Ok(SlotPolicy::RoundRobin));
+        assert!(SlotPolicy::from_spec("lifo").is_err());
+    }
+}
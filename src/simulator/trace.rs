@@ -0,0 +1,352 @@
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// A single packet arrival in a trace.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TraceRecord {
+    /// Tick the packet arrives in.
+    pub t: u64,
+    pub tenant: usize,
+    /// Number of ticks it needs to complete, given requested resources.
+    pub service_time: f64,
+    /// Resources requested
+    pub resource_req: Vec<f64>,
+    /// Application of the tenant the packet belongs to, 0 in traces that
+    /// leave it out, as are the flow and class.
+    #[serde(default)]
+    pub app: usize,
+    /// Flow of the application the packet belongs to.
+    #[serde(default)]
+    pub flow: usize,
+    /// Size in bytes, DEFAULT_TRACE_SIZE in traces that leave it out.
+    #[serde(default = "default_size")]
+    pub size: u64,
+    /// Traffic class, see Packet::class().
+    #[serde(default)]
+    pub class: u8,
+}
+
+impl TraceRecord {
+    /// Checks that the record describes a packet that can be served: its
+    /// service time and resource requests are finite and not negative.
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.service_time.is_finite() && self.service_time >= 0.0) {
+            return Err(format!(
+                "service_time {} must be finite and not negative",
+                self.service_time
+            ));
+        }
+        if let Some(req) = self
+            .resource_req
+            .iter()
+            .find(|req| !(req.is_finite() && **req >= 0.0))
+        {
+            return Err(format!(
+                "resource request {} must be finite and not negative",
+                req
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Size in bytes of the packets of a trace that leaves it out, the one
+/// DEFAULT_SIZE gives generated packets.
+pub const DEFAULT_TRACE_SIZE: u64 = 1500;
+
+fn default_size() -> u64 {
+    DEFAULT_TRACE_SIZE
+}
+
+/// Columns a CSV trace starts with, followed by one column per resource.
+const CSV_COLUMNS: [&str; 3] = ["t", "tenant", "service_time"];
+
+/// On-disk trace formats, chosen by file extension.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TraceFormat {
+    /// `.csv`: a `t,tenant,service_time,...` header line, then
+    /// `t,tenant,service_time,r0,r1,...` per packet. Columns after
+    /// `service_time` named `app`, `flow`, `size` or `class` in the header
+    /// fill those fields, the others are resources.
+    Csv,
+    /// `.json` or `.jsonl`: one JSON object per line with the fields of
+    /// TraceRecord.
+    JsonLines,
+}
+
+impl TraceFormat {
+    pub fn from_path(path: &str) -> io::Result<TraceFormat> {
+        match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+            Some("csv") => Ok(TraceFormat::Csv),
+            Some("json") | Some("jsonl") => Ok(TraceFormat::JsonLines),
+            _ => Err(invalid_data(format!(
+                "{}: unknown trace format, expected .csv, .json or .jsonl",
+                path
+            ))),
+        }
+    }
+}
+
+/// Reads all records of a trace, sorted by arrival tick. Records arriving in
+/// the same tick keep their order in the file.
+pub fn read_trace(path: &str) -> io::Result<Vec<TraceRecord>> {
+    let format = TraceFormat::from_path(path)?;
+    parse_trace(BufReader::new(File::open(path)?), format)
+}
+
+pub fn parse_trace<R: BufRead>(reader: R, format: TraceFormat) -> io::Result<Vec<TraceRecord>> {
+    let mut records: Vec<TraceRecord> = Vec::new();
+    let mut header: Vec<String> = Vec::new();
+    for (lineno, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let record = match format {
+            TraceFormat::Csv if header.is_empty() => {
+                header = parse_csv_header(line)
+                    .map_err(|e| invalid_data(format!("line {}: {}", lineno + 1, e)))?;
+                continue;
+            }
+            TraceFormat::Csv => parse_csv_record(line, &header),
+            TraceFormat::JsonLines => serde_json::from_str(line).map_err(|e| e.to_string()),
+        }
+        .and_then(|record: TraceRecord| record.validate().map(|_| record))
+        .map_err(|e| invalid_data(format!("line {}: {}", lineno + 1, e)))?;
+        records.push(record);
+    }
+    records.sort_by_key(|record| record.t);
+    Ok(records)
+}
+
+/// Reads the column names of a header line, checking that `line` is a
+/// header rather than a packet, so that the first packet of a trace without
+/// a header is not skipped.
+fn parse_csv_header(line: &str) -> Result<Vec<String>, String> {
+    let columns: Vec<String> = line
+        .split(',')
+        .map(|column| column.trim().to_string())
+        .collect();
+    if columns.len() > CSV_COLUMNS.len() && columns[..CSV_COLUMNS.len()] == CSV_COLUMNS {
+        Ok(columns)
+    } else {
+        Err(format!(
+            "expected a {},... header, got {}",
+            CSV_COLUMNS.join(","),
+            line
+        ))
+    }
+}
+
+/// Parses a CSV line whose columns are named by `header`.
+fn parse_csv_record(line: &str, header: &[String]) -> Result<TraceRecord, String> {
+    let fields: Vec<&str> = line.split(',').map(|field| field.trim()).collect();
+    let mut record = TraceRecord {
+        t: 0,
+        tenant: 0,
+        service_time: 0.0,
+        resource_req: Vec::new(),
+        app: 0,
+        flow: 0,
+        size: DEFAULT_TRACE_SIZE,
+        class: 0,
+    };
+    if fields.len() >= 3 {
+        record.t = fields[0].parse().map_err(|e| format!("t: {}", e))?;
+        record.tenant = fields[1].parse().map_err(|e| format!("tenant: {}", e))?;
+        record.service_time = fields[2]
+            .parse()
+            .map_err(|e| format!("service_time: {}", e))?;
+    }
+    for (i, field) in fields.iter().enumerate().skip(3) {
+        let name = header.get(i).map_or("", String::as_str);
+        let field_err = |e: std::num::ParseIntError| format!("{}: {}", name, e);
+        match name {
+            "app" => record.app = field.parse().map_err(field_err)?,
+            "flow" => record.flow = field.parse().map_err(field_err)?,
+            "size" => record.size = field.parse().map_err(field_err)?,
+            "class" => record.class = field.parse().map_err(field_err)?,
+            _ => record
+                .resource_req
+                .push(field.parse::<f64>().map_err(|e| e.to_string())?),
+        }
+    }
+    if record.resource_req.is_empty() {
+        return Err(format!(
+            "expected t,tenant,service_time and at least one resource, got {}",
+            line
+        ));
+    }
+    Ok(record)
+}
+
+/// Records packets as they are generated, in a format read_trace() reads
+/// back.
+pub struct TraceWriter {
+    out: BufWriter<File>,
+    format: TraceFormat,
+    header_written: bool,
+}
+
+impl TraceWriter {
+    pub fn create(path: &str) -> io::Result<TraceWriter> {
+        let format = TraceFormat::from_path(path)?;
+        Ok(TraceWriter {
+            out: BufWriter::new(File::create(path)?),
+            format,
+            header_written: false,
+        })
+    }
+
+    pub fn write(&mut self, record: &TraceRecord) -> io::Result<()> {
+        match self.format {
+            TraceFormat::Csv => {
+                if !self.header_written {
+                    let resources: Vec<String> = (0..record.resource_req.len())
+                        .map(|j| format!("r{}", j))
+                        .collect();
+                    writeln!(
+                        self.out,
+                        "{},app,flow,size,class,{}",
+                        CSV_COLUMNS.join(","),
+                        resources.join(",")
+                    )?;
+                    self.header_written = true;
+                }
+                let resources: Vec<String> =
+                    record.resource_req.iter().map(|x| x.to_string()).collect();
+                writeln!(
+                    self.out,
+                    "{},{},{},{},{},{},{},{}",
+                    record.t,
+                    record.tenant,
+                    record.service_time,
+                    record.app,
+                    record.flow,
+                    record.size,
+                    record.class,
+                    resources.join(",")
+                )
+            }
+            TraceFormat::JsonLines => {
+                serde_json::to_writer(&mut self.out, record)?;
+                writeln!(self.out)
+            }
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::simulator::{distribution_from_spec, DEFAULT_SIZE};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn records() -> Vec<TraceRecord> {
+        vec![
+            TraceRecord {
+                t: 0,
+                tenant: 1,
+                service_time: 12.0,
+                resource_req: vec![3.0, 5.5],
+                app: 0,
+                flow: 0,
+                size: DEFAULT_TRACE_SIZE,
+                class: 0,
+            },
+            TraceRecord {
+                t: 4,
+                tenant: 0,
+                service_time: 10.0,
+                resource_req: vec![1.0, 2.0],
+                app: 0,
+                flow: 0,
+                size: DEFAULT_TRACE_SIZE,
+                class: 0,
+            },
+        ]
+    }
+
+    #[test]
+    fn parse_csv() {
+        let csv = "t,tenant,service_time,r0,r1\n4,0,10,1,2\n0,1,12,3,5.5\n";
+        let parsed = parse_trace(csv.as_bytes(), TraceFormat::Csv).unwrap();
+        assert_eq!(parsed, records());
+        let header = "t,tenant,service_time,r0\n";
+        for body in &["0,1,12\n", "0,x,12,1\n", "0,1,-12,1\n", "0,1,12,NaN\n"] {
+            let csv = format!("{}{}", header, body);
+            assert!(
+                parse_trace(csv.as_bytes(), TraceFormat::Csv).is_err(),
+                "{}",
+                csv
+            );
+        }
+        // Without a header, the first packet would go missing.
+        assert!(parse_trace("4,0,10,1,2\n".as_bytes(), TraceFormat::Csv).is_err());
+        let csv = "t,tenant,service_time,r0,size,class\n0,1,12,3,64,46\n";
+        let parsed = parse_trace(csv.as_bytes(), TraceFormat::Csv).unwrap();
+        assert_eq!(parsed[0].resource_req, [3.0]);
+        assert_eq!((parsed[0].size, parsed[0].class), (64, 46));
+        let csv = "t,tenant,service_time,size\n0,1,12,64\n";
+        assert!(parse_trace(csv.as_bytes(), TraceFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn parse_json_lines() {
+        let json = r#"{"t": 4, "tenant": 0, "service_time": 10, "resource_req": [1, 2]}
+
+{"t": 0, "tenant": 1, "service_time": 12, "resource_req": [3, 5.5]}
+"#;
+        let parsed = parse_trace(json.as_bytes(), TraceFormat::JsonLines).unwrap();
+        assert_eq!(parsed, records());
+        assert!(parse_trace("{\"t\": 0}".as_bytes(), TraceFormat::JsonLines).is_err());
+        let negative = r#"{"t": 0, "tenant": 0, "service_time": 1, "resource_req": [-1]}"#;
+        let error = parse_trace(negative.as_bytes(), TraceFormat::JsonLines).unwrap_err();
+        assert!(error.to_string().starts_with("line 1:"), "{}", error);
+    }
+
+    #[test]
+    fn round_trip() {
+        for ext in &["csv", "jsonl"] {
+            let path = std::env::temp_dir().join(format!("denarii_test_trace.{}", ext));
+            let path = path.to_str().unwrap();
+            let mut records = records();
+            records[0].app = 1;
+            records[0].flow = 2;
+            records[0].size = 64;
+            records[0].class = 3;
+            let mut writer = TraceWriter::create(path).unwrap();
+            for record in &records {
+                writer.write(record).unwrap();
+            }
+            writer.flush().unwrap();
+            assert_eq!(read_trace(path).unwrap(), records);
+        }
+    }
+
+    #[test]
+    fn default_trace_size() {
+        let size = distribution_from_spec(DEFAULT_SIZE).unwrap();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        assert_eq!(size.sample(&mut rng), DEFAULT_TRACE_SIZE as f64);
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert!(TraceFormat::from_path("trace.pcap").is_err());
+        assert!(TraceFormat::from_path("trace").is_err());
+    }
+}
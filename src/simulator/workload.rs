@@ -0,0 +1,309 @@
+use super::{
+    arrival_process_from_spec, read_trace, ArrivalProcess, DemandProfile, Empirical, TenantConfig,
+    TraceRecord, TraceWriter, ValueDistribution,
+};
+use rand::rngs::StdRng;
+use rand::Rng;
+use std::iter::Peekable;
+use std::vec::IntoIter;
+
+/// Where new packets come from: either generated from the tenants'
+/// distributions or replayed from a trace. Optionally records every packet it
+/// hands out.
+pub struct Workload {
+    arrivals: Vec<Box<dyn ArrivalProcess>>,
+    service_times: Vec<Box<dyn ValueDistribution>>,
+    demands: Vec<Box<dyn DemandProfile>>,
+    sizes: Vec<Box<dyn ValueDistribution>>,
+    apps: Vec<Apps>,
+    /// Packets replayed from a trace, sorted by arrival tick.
+    trace: Option<Peekable<IntoIter<TraceRecord>>>,
+    recorder: Option<TraceWriter>,
+    num_resources: usize,
+    num_tenants: usize,
+}
+
+/// Applications of a tenant, see AppConfig.
+struct Apps {
+    /// Distribution of the application index, None without applications.
+    distribution: Option<Empirical>,
+    flows: Vec<usize>,
+    /// Demand profiles overriding the tenant's.
+    demands: Vec<Option<Box<dyn DemandProfile>>>,
+    /// Traffic class of every application, falling back to the tenant's.
+    classes: Vec<u8>,
+    /// Traffic class of the tenant.
+    class: u8,
+}
+
+impl Apps {
+    fn new(tenant: &TenantConfig) -> Result<Apps, String> {
+        Ok(Apps {
+            distribution: tenant.app_distribution()?,
+            flows: tenant.apps.iter().map(|app| app.flows).collect(),
+            demands: tenant.app_demand_profiles()?,
+            classes: tenant
+                .apps
+                .iter()
+                .map(|app| app.class.unwrap_or(tenant.class))
+                .collect(),
+            class: tenant.class,
+        })
+    }
+
+    /// Traffic class of the packets of `app`.
+    fn class(&self, app: usize) -> u8 {
+        self.classes.get(app).copied().unwrap_or(self.class)
+    }
+
+    /// Picks the application and flow of a packet.
+    fn sample(&self, rng: &mut StdRng) -> (usize, usize) {
+        match &self.distribution {
+            Some(distribution) => {
+                let app = distribution.sample(rng) as usize;
+                (app, rng.gen_range(0, self.flows[app]))
+            }
+            None => (0, 0),
+        }
+    }
+}
+
+impl Workload {
+    /// Generates packets of `tenants`, or replays the trace at `trace` if
+    /// given. Every packet is recorded to `record` if given.
+    pub fn new(
+        tenants: &[TenantConfig],
+        num_resources: usize,
+        trace: Option<&str>,
+        record: Option<&str>,
+    ) -> Result<Workload, String> {
+        let trace = match trace {
+            Some(path) => Some(
+                read_trace(path).map_err(|e| format!("failed to read trace {}: {}", path, e))?,
+            ),
+            None => None,
+        };
+        let num_tenants = match &trace {
+            Some(records) => records.iter().map(|record| record.tenant + 1).max(),
+            None => Some(tenants.len()),
+        }
+        .unwrap_or(0);
+        let recorder = match record {
+            Some(path) => Some(
+                TraceWriter::create(path)
+                    .map_err(|e| format!("failed to create trace {}: {}", path, e))?,
+            ),
+            None => None,
+        };
+        Ok(Workload {
+            arrivals: tenants
+                .iter()
+                .map(|tenant| arrival_process_from_spec(&tenant.arrivals))
+                .collect::<Result<Vec<_>, _>>()?,
+            service_times: tenants
+                .iter()
+                .map(|tenant| tenant.service_time_distribution())
+                .collect::<Result<Vec<_>, _>>()?,
+            demands: tenants
+                .iter()
+                .map(|tenant| {
+                    tenant
+                        .check_resources(num_resources)
+                        .and(tenant.demand_profile())
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            sizes: tenants
+                .iter()
+                .map(|tenant| tenant.size_distribution())
+                .collect::<Result<Vec<_>, _>>()?,
+            apps: tenants
+                .iter()
+                .map(Apps::new)
+                .collect::<Result<Vec<_>, _>>()?,
+            trace: trace.map(|records| records.into_iter().peekable()),
+            recorder,
+            num_resources,
+            num_tenants,
+        })
+    }
+
+    /// Number of tenants packets come from, counting those of a trace up to
+    /// the highest tenant index in it.
+    pub fn num_tenants(&self) -> usize {
+        self.num_tenants
+    }
+
+    /// Returns the first tick from `t` on and before `end` in which packets
+    /// arrive, along with those packets, or None if no more packets arrive
+    /// before `end`. A trace skips straight to its next record. Ticks without
+    /// arrivals are consumed, so the next call must start after the returned
+    /// tick.
+    pub fn next_arrivals(
+        &mut self,
+        t: u64,
+        end: u64,
+        rng: &mut StdRng,
+    ) -> Option<(u64, Vec<TraceRecord>)> {
+        let mut t = t;
+        if let Some(trace) = self.trace.as_mut() {
+            t = t.max(trace.peek()?.t);
+        }
+        while t < end {
+            let new_pkts = self.arrivals(t, rng);
+            if !new_pkts.is_empty() {
+                return Some((t, new_pkts));
+            }
+            t += 1;
+        }
+        None
+    }
+
+    /// Returns the packets arriving in tick `t`. Must be called for every
+    /// tick in order, as arrival processes keep state between ticks.
+    pub fn arrivals(&mut self, t: u64, rng: &mut StdRng) -> Vec<TraceRecord> {
+        let mut new_pkts: Vec<TraceRecord> = Vec::new();
+        match self.trace.as_mut() {
+            Some(trace) => {
+                while let Some(record) = trace.next_if(|record| record.t <= t) {
+                    assert!(
+                        record.resource_req.len() == self.num_resources,
+                        "trace has {} resources, capacity has {}",
+                        record.resource_req.len(),
+                        self.num_resources
+                    );
+                    new_pkts.push(TraceRecord { t, ..record });
+                }
+            }
+            None => {
+                for (tenant, process) in self.arrivals.iter_mut().enumerate() {
+                    for _ in 0..process.arrivals(t, rng) {
+                        let apps = &self.apps[tenant];
+                        let (app, flow) = apps.sample(rng);
+                        let demand = match apps.demands.get(app) {
+                            Some(Some(demand)) => demand,
+                            _ => &self.demands[tenant],
+                        };
+                        let service_time = self.service_times[tenant].sample(rng);
+                        let resource_req = demand.sample(self.num_resources, rng);
+                        let size = self.sizes[tenant].sample(rng).round().max(0.0) as u64;
+                        new_pkts.push(TraceRecord {
+                            t,
+                            tenant,
+                            service_time,
+                            resource_req,
+                            app,
+                            flow,
+                            size,
+                            class: apps.class(app),
+                        });
+                    }
+                }
+            }
+        }
+
+        if let Some(recorder) = self.recorder.as_mut() {
+            for new_pkt in &new_pkts {
+                recorder
+                    .write(new_pkt)
+                    .unwrap_or_else(|e| panic!("failed to record trace: {}", e));
+            }
+        }
+        new_pkts
+    }
+
+    /// Flushes the recorded trace.
+    pub fn finish(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder
+                .flush()
+                .unwrap_or_else(|e| panic!("failed to record trace: {}", e));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    #[test]
+    fn generated() {
+        let tenants = vec![
+            TenantConfig::new("periodic:2"),
+            TenantConfig::new("periodic:3"),
+        ];
+        let mut workload = Workload::new(&tenants, 2, None, None).unwrap();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let tenants: Vec<Vec<usize>> = (0..4)
+            .map(|t| {
+                workload
+                    .arrivals(t, &mut rng)
+                    .iter()
+                    .map(|pkt| pkt.tenant)
+                    .collect()
+            })
+            .collect();
+        assert_eq!(tenants, [vec![0, 1], vec![], vec![0], vec![1]]);
+        assert!(Workload::new(&[TenantConfig::new("poisson")], 2, None, None).is_err());
+    }
+
+    #[test]
+    fn next_arrivals() {
+        let tenants = vec![
+            TenantConfig::new("periodic:5,2"),
+            TenantConfig::new("periodic:7"),
+        ];
+        let mut workload = Workload::new(&tenants, 1, None, None).unwrap();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let mut ticks = Vec::new();
+        let mut t = 0;
+        while let Some((next, pkts)) = workload.next_arrivals(t, 15, &mut rng) {
+            ticks.push((next, pkts.len()));
+            t = next + 1;
+        }
+        assert_eq!(ticks, [(0, 1), (2, 1), (7, 2), (12, 1), (14, 1)]);
+    }
+
+    #[test]
+    fn trace_tenants() {
+        let path = std::env::temp_dir().join("denarii_test_workload.jsonl");
+        let path = path.to_str().unwrap();
+        std::fs::write(
+            path,
+            r#"{"t": 0, "tenant": 3, "service_time": 1, "resource_req": [1]}
+{"t": 9, "tenant": 0, "service_time": 1, "resource_req": [1]}"#,
+        )
+        .unwrap();
+        let mut workload =
+            Workload::new(&[TenantConfig::new("periodic:1")], 1, Some(path), None).unwrap();
+        assert_eq!(workload.num_tenants(), 4);
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let next = workload.next_arrivals(0, 20, &mut rng).unwrap();
+        assert_eq!((next.0, next.1[0].tenant), (0, 3));
+        let next = workload.next_arrivals(1, 20, &mut rng).unwrap();
+        assert_eq!((next.0, next.1[0].tenant), (9, 0));
+        assert!(workload.next_arrivals(10, 20, &mut rng).is_none());
+    }
+
+    #[test]
+    fn apps() {
+        let mut tenant = TenantConfig::new("periodic:1");
+        tenant.class = 2;
+        tenant.apps = serde_json::from_str(
+            r#"[{"weight": 0, "class": 5}, {"flows": 3, "demand": "const:4"}]"#,
+        )
+        .unwrap();
+        let mut workload = Workload::new(&[tenant], 1, None, None).unwrap();
+        let mut rng: StdRng = SeedableRng::seed_from_u64(1);
+        let mut flows = [false; 3];
+        for t in 0..50 {
+            for pkt in workload.arrivals(t, &mut rng) {
+                assert_eq!(pkt.app, 1);
+                assert_eq!(pkt.resource_req, [4.0]);
+                assert_eq!(pkt.class, 2);
+                flows[pkt.flow] = true;
+            }
+        }
+        assert_eq!(flows, [true; 3]);
+    }
+}
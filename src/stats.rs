@@ -0,0 +1,88 @@
+/// Two-sided 95% critical values of Student's t distribution, indexed by
+/// degrees of freedom minus one.
+const T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+pub fn mean(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+/// Sample standard deviation, 0 for fewer than two samples.
+pub fn std_dev(samples: &[f64]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let m = mean(samples);
+    let var = samples.iter().map(|x| (x - m) * (x - m)).sum::<f64>() / (samples.len() - 1) as f64;
+    var.sqrt()
+}
+
+/// Returns the mean of `samples` and the half width of its 95% confidence
+/// interval. The interval is 0 wide for fewer than two samples.
+pub fn confidence_interval(samples: &[f64]) -> (f64, f64) {
+    let n = samples.len();
+    if n < 2 {
+        return (mean(samples), 0.0);
+    }
+    let t = if n - 1 <= T_95.len() {
+        T_95[n - 2]
+    } else {
+        1.96
+    };
+    (mean(samples), t * std_dev(samples) / (n as f64).sqrt())
+}
+
+/// Formats samples as `mean ± half width` of their 95% confidence interval.
+pub fn format_ci(samples: &[f64]) -> String {
+    let (mean, half_width) = confidence_interval(samples);
+    format!("{:.2} ± {:.2}", mean, half_width)
+}
+
+/// Jain's fairness index of `values`, from 1/n when one value takes
+/// everything to 1 when all are equal. 1 if there are no values or all are
+/// 0.
+pub fn jain_index(values: &[f64]) -> f64 {
+    let sum: f64 = values.iter().sum();
+    let sum_of_squares: f64 = values.iter().map(|x| x * x).sum();
+    if sum_of_squares <= 0.0 {
+        return 1.0;
+    }
+    sum * sum / (values.len() as f64 * sum_of_squares)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use float_cmp::approx_eq;
+
+    #[test]
+    fn simple() {
+        let samples = [2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0];
+        assert!(approx_eq!(f64, mean(&samples), 5.0, epsilon = 1e-9));
+        assert!(approx_eq!(f64, std_dev(&samples), 2.138, epsilon = 0.001));
+        let (m, hw) = confidence_interval(&samples);
+        assert!(approx_eq!(f64, m, 5.0, epsilon = 1e-9));
+        // t(7) = 2.365, 2.138 / sqrt(8) = 0.756
+        assert!(approx_eq!(f64, hw, 1.788, epsilon = 0.001));
+    }
+
+    #[test]
+    fn few_samples() {
+        assert_eq!(confidence_interval(&[]), (0.0, 0.0));
+        assert_eq!(confidence_interval(&[3.0]), (3.0, 0.0));
+        assert_eq!(format_ci(&[3.0]), "3.00 ± 0.00");
+    }
+
+    #[test]
+    fn jain() {
+        assert_eq!(jain_index(&[2.0, 2.0, 2.0]), 1.0);
+        assert_eq!(jain_index(&[1.0, 0.0, 0.0, 0.0]), 0.25);
+        assert_eq!(jain_index(&[]), 1.0);
+    }
+}
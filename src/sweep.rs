@@ -0,0 +1,185 @@
+use crate::gurobi;
+use crate::simulator::{self, Engine, Execution, SimConfig, SimResult, TenantConfig};
+use crate::stats::format_ci;
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::thread;
+
+/// A grid of simulation runs: every combination of algorithm, arrival
+/// process and capacity, each run once per seed.
+#[derive(Clone, Debug)]
+pub struct SweepConfig {
+    pub algorithms: Vec<String>,
+    /// Arrival process of the single tenant at each grid point.
+    pub arrivals: Vec<String>,
+    pub capacities: Vec<Vec<f64>>,
+    /// Seeds 1 to `seeds` are run at every grid point.
+    pub seeds: u64,
+    pub ticks: u64,
+    pub engine: Engine,
+    pub execution: Execution,
+}
+
+impl SweepConfig {
+    /// Every run of the grid. Seeds vary fastest, so runs of the same grid
+    /// point are adjacent.
+    pub fn jobs(&self) -> Vec<SimConfig> {
+        let mut jobs: Vec<SimConfig> = Vec::new();
+        for alg_name in &self.algorithms {
+            for spec in &self.arrivals {
+                for capacity in &self.capacities {
+                    for seed in 1..=self.seeds {
+                        jobs.push(SimConfig {
+                            engine: self.engine,
+                            execution: self.execution,
+                            ..SimConfig::new(
+                                alg_name,
+                                vec![TenantConfig::new(spec)],
+                                capacity.clone(),
+                                seed,
+                                self.ticks,
+                            )
+                        });
+                    }
+                }
+            }
+        }
+        jobs
+    }
+
+    /// Runs every job on `num_threads` worker threads, returning the results
+    /// in the order of jobs().
+    pub fn run(&self, num_threads: usize) -> Result<Vec<SimResult>, String> {
+        run_parallel(self.jobs(), num_threads, |config| {
+            simulator::simulate(&config, false, None)
+        })
+        .into_iter()
+        .collect()
+    }
+
+    /// Writes a tab separated table with one line per grid point, holding the
+    /// mean and 95% confidence interval over seeds of `results`.
+    pub fn write_summary(&self, results: &[SimResult], out: &mut impl Write) -> io::Result<()> {
+        writeln!(
+            out,
+            "algorithm\tarrivals\tcapacity\truns\tpackets\tdropped\tcompleted\tmean_latency\tlatency_runs"
+        )?;
+        let num_seeds = self.seeds.max(1) as usize;
+        for (configs, results) in self.jobs().chunks(num_seeds).zip(results.chunks(num_seeds)) {
+            let packets: Vec<f64> = results.iter().map(|r| r.num_pkts as f64).collect();
+            let dropped: Vec<f64> = results.iter().map(|r| r.num_drops() as f64).collect();
+            let completed: Vec<f64> = results.iter().map(|r| r.completed.len() as f64).collect();
+            // Runs that completed nothing have no latency, rather than 0.
+            let latency: Vec<f64> = results.iter().filter_map(|r| r.mean_latency()).collect();
+            writeln!(
+                out,
+                "{}\t{}\t{:?}\t{}\t{}\t{}\t{}\t{}\t{}",
+                configs[0].algorithm,
+                configs[0]
+                    .tenants
+                    .iter()
+                    .map(|tenant| tenant.arrivals.as_str())
+                    .collect::<Vec<&str>>()
+                    .join(" "),
+                configs[0].capacity,
+                results.len(),
+                format_ci(&packets),
+                format_ci(&dropped),
+                format_ci(&completed),
+                if latency.is_empty() {
+                    "-".to_string()
+                } else {
+                    format_ci(&latency)
+                },
+                latency.len()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs `f` on every job using `num_threads` worker threads, and returns the
+/// results in the same order as `jobs`.
+///
+/// Jobs are handed out one at a time, so long and short jobs balance out
+/// across threads. Anything `f` needs from Gurobi has to be created inside
+/// `f`, as every thread needs its own optimizer. Optimizers do not log on the
+/// worker threads, see gurobi::set_logging().
+pub fn run_parallel<J, R, F>(jobs: Vec<J>, num_threads: usize, f: F) -> Vec<R>
+where
+    J: Send,
+    R: Send,
+    F: Fn(J) -> R + Sync,
+{
+    let num_jobs = jobs.len();
+    let queue = Mutex::new(jobs.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(num_jobs));
+
+    thread::scope(|s| {
+        for _ in 0..num_threads.max(1).min(num_jobs) {
+            s.spawn(|| {
+                gurobi::set_logging(false);
+                loop {
+                    // Hold the lock only while taking the next job.
+                    let next = queue.lock().unwrap().next();
+                    match next {
+                        Some((i, job)) => {
+                            let result = f(job);
+                            results.lock().unwrap().push((i, result));
+                        }
+                        None => break,
+                    }
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preserves_order() {
+        let jobs: Vec<u64> = (0..100).collect();
+        let results = run_parallel(jobs, 4, |x| x * x);
+        assert_eq!(results, (0..100).map(|x| x * x).collect::<Vec<u64>>());
+    }
+
+    #[test]
+    fn more_threads_than_jobs() {
+        assert_eq!(run_parallel(vec![1, 2], 8, |x| x + 1), [2, 3]);
+        assert!(run_parallel(Vec::<u64>::new(), 8, |x| x).is_empty());
+    }
+
+    #[test]
+    fn jobs() {
+        let config = SweepConfig {
+            algorithms: vec!["drf".to_string(), "ceei".to_string()],
+            arrivals: vec!["poisson:1".to_string()],
+            capacities: vec![vec![10.0], vec![20.0]],
+            seeds: 3,
+            ticks: 10,
+            engine: Engine::Event,
+            execution: Execution::Drfq,
+        };
+        let jobs = config.jobs();
+        assert_eq!(jobs.len(), 12);
+        assert_eq!(
+            jobs.iter()
+                .take(4)
+                .map(|job| job.seed)
+                .collect::<Vec<u64>>(),
+            [1, 2, 3, 1]
+        );
+        assert_eq!(jobs[3].capacity, [20.0]);
+        assert_eq!(jobs[6].algorithm, "ceei");
+        assert!(jobs
+            .iter()
+            .all(|job| job.engine == Engine::Event && job.execution == Execution::Drfq));
+    }
+}